@@ -43,7 +43,7 @@ use std::sync::{Arc, Mutex, RwLock};
 use log::debug;
 use unicode_width::UnicodeWidthStr;
 
-use crate::tty::{RawMode, Renderer, Term, Terminal};
+use crate::tty::{RawMode, RawReader, Renderer, Term, Terminal};
 
 use crate::completion::{longest_common_prefix, Candidate, Completer};
 pub use crate::config::{
@@ -419,6 +419,7 @@ fn readline_edit<H: Helper>(
     initial: Option<(&str, &str)>,
     editor: &mut Editor<H>,
     original_mode: &tty::Mode,
+    mut listener: Option<&mut LineListener<'_>>,
 ) -> Result<String> {
     let helper = editor.helper.as_ref();
 
@@ -449,7 +450,21 @@ fn readline_edit<H: Helper>(
     }
     s.refresh_line()?;
 
+    if let Some(ref mut listener) = listener {
+        listener.last_line = s.line.as_str().to_owned();
+    }
+
     loop {
+        if let Some(ref mut listener) = listener {
+            if s.line.as_str() != listener.last_line
+                && !rdr.wait_for_input(listener.debounce_ms)?
+            {
+                listener.last_line = s.line.as_str().to_owned();
+                (listener.on_change)(s.line.as_str())?;
+                // The listener may have drawn over the line
+                s.refresh_line()?;
+            }
+        }
         let rc = s.next_cmd(&mut input_state, &mut rdr, true);
         let mut cmd = rc?;
 
@@ -564,7 +579,6 @@ fn readline_edit<H: Helper>(
             #[cfg(unix)]
             Cmd::QuotedInsert => {
                 // Quoted insert
-                use tty::RawReader;
                 let c = rdr.next_char()?;
                 s.edit_insert(c, 1)?
             }
@@ -683,6 +697,14 @@ fn readline_edit<H: Helper>(
     Ok(s.line.into_string())
 }
 
+/// Callback run when the line being edited changes, see
+/// `Editor::readline_with_listener`.
+struct LineListener<'l> {
+    debounce_ms: i32,
+    on_change: &'l mut dyn FnMut(&str) -> Result<()>,
+    last_line: String,
+}
+
 struct Guard<'m>(&'m tty::Mode);
 
 #[allow(unused_must_use)]
@@ -699,10 +721,11 @@ fn readline_raw<H: Helper>(
     prompt: &str,
     initial: Option<(&str, &str)>,
     editor: &mut Editor<H>,
+    listener: Option<&mut LineListener<'_>>,
 ) -> Result<String> {
     let original_mode = editor.term.enable_raw_mode()?;
     let guard = Guard(&original_mode);
-    let user_input = readline_edit(prompt, initial, editor, &original_mode);
+    let user_input = readline_edit(prompt, initial, editor, &original_mode, listener);
     if editor.config.auto_add_history() {
         if let Ok(ref line) = user_input {
             editor.add_history_entry(line.as_str());
@@ -803,7 +826,7 @@ impl<H: Helper> Editor<H> {
     /// Otherwise (e.g., if `stdin` is a pipe or the terminal is not supported),
     /// it uses file-style interaction.
     pub fn readline(&mut self, prompt: &str) -> Result<String> {
-        self.readline_with(prompt, None)
+        self.readline_with(prompt, None, None)
     }
 
     /// This function behaves in the exact same manner as `readline`, except
@@ -814,10 +837,38 @@ impl<H: Helper> Editor<H> {
     /// the cursor and the string on the right is what will appear to the
     /// right of the cursor.
     pub fn readline_with_initial(&mut self, prompt: &str, initial: (&str, &str)) -> Result<String> {
-        self.readline_with(prompt, Some(initial))
+        self.readline_with(prompt, Some(initial), None)
+    }
+
+    /// This function behaves in the exact same manner as
+    /// `readline_with_initial`, except that `on_change` is called with the
+    /// line whenever it is edited.
+    ///
+    /// Calls are debounced: `on_change` only runs once no key has been
+    /// pressed for `debounce_ms`. It may draw on the terminal, as long as it
+    /// leaves the cursor on the row being edited: the line is redrawn
+    /// afterwards.
+    pub fn readline_with_listener(
+        &mut self,
+        prompt: &str,
+        initial: (&str, &str),
+        debounce_ms: i32,
+        on_change: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<String> {
+        let mut listener = LineListener {
+            debounce_ms,
+            on_change,
+            last_line: String::new(),
+        };
+        self.readline_with(prompt, Some(initial), Some(&mut listener))
     }
 
-    fn readline_with(&mut self, prompt: &str, initial: Option<(&str, &str)>) -> Result<String> {
+    fn readline_with(
+        &mut self,
+        prompt: &str,
+        initial: Option<(&str, &str)>,
+        listener: Option<&mut LineListener<'_>>,
+    ) -> Result<String> {
         if self.term.is_unsupported() {
             debug!(target: "rustyline", "unsupported terminal");
             // Write prompt and flush it to stdout
@@ -827,7 +878,7 @@ impl<H: Helper> Editor<H> {
 
             readline_direct()
         } else if self.term.is_stdin_tty() {
-            readline_raw(prompt, initial, self)
+            readline_raw(prompt, initial, self, listener)
         } else {
             debug!(target: "rustyline", "stdin is not a tty");
             // Not a tty: read from file / pipe.
@@ -114,6 +114,26 @@ fn assert_history(
     }
 }
 
+#[test]
+fn line_listener() {
+    let keys = [
+        KeyPress::Char('b'),
+        KeyPress::Left,
+        KeyPress::Char('a'),
+        KeyPress::Enter,
+    ];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    let mut lines = Vec::new();
+    let actual_line = editor
+        .readline_with_listener(">>", ("c", ""), 0, &mut |line| {
+            lines.push(line.to_owned());
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!("cab", actual_line);
+    assert_eq!(vec!["cb", "cab"], lines);
+}
+
 #[test]
 fn unknown_esc_key() {
     for mode in &[EditMode::Emacs, EditMode::Vi] {
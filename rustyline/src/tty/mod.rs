@@ -24,6 +24,11 @@ pub trait RawReader {
     fn next_char(&mut self) -> Result<char>;
     /// Bracketed paste
     fn read_pasted_text(&mut self) -> Result<String>;
+    /// Wait at most `timeout_ms` for input, returning whether any is
+    /// available. Readers that can't tell report that there is none.
+    fn wait_for_input(&mut self, _timeout_ms: i32) -> Result<bool> {
+        Ok(false)
+    }
 }
 
 /// Display prompt, line and cursor in terminal output
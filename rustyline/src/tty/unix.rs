@@ -396,6 +396,10 @@ impl PosixRawReader {
 }
 
 impl RawReader for PosixRawReader {
+    fn wait_for_input(&mut self, timeout_ms: i32) -> Result<bool> {
+        Ok(self.poll(timeout_ms)? != 0)
+    }
+
     fn next_key(&mut self, single_esc_abort: bool) -> Result<KeyPress> {
         let c = self.next_char()?;
 
@@ -1,14 +1,21 @@
 use crate::{
-    cursor::GlobalCursor,
+    cursor::{GlobalCursor, LeafCursor},
+    diff::Diff,
+    edit::Edit,
+    format::{Format, Location},
+    jq::{jv::JV, query::JQ},
     layout::{self, JexLayout},
+    loader::{LoadError, Loader},
+    search::{MatchCount, Matches, Search},
     view_tree::{View, ViewFrame, ViewTree, ViewTreeIndex},
 };
 use log::debug;
-use regex::Regex;
-use std::{default::Default, io};
+use serde_json::Deserializer;
+use std::{collections::HashMap, default::Default, io, rc::Rc};
 use tui::{
     layout::{Alignment, Rect},
-    text::Text,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans, Text},
     widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
@@ -19,9 +26,21 @@ pub struct App {
     pub views: ViewTree,
     pub index: ViewTreeIndex,
     pub focus: Focus,
-    pub search_re: Option<Regex>,
+    pub search: Option<Search>,
+    // Every match of the search in the view it was last run in, until its values change
+    search_matches: Option<(ViewTreeIndex, Focus, Matches)>,
+    // Where the last search left the cursor, shown while it stays there
+    pub search_count: Option<(ViewTreeIndex, Focus, MatchCount)>,
     pub show_tree: bool,
     pub flash: Option<Flash>,
+    // Set until the json has finished loading
+    pub loader: Option<Loader>,
+    // The queries run on each batch as it loads
+    programs: HashMap<String, JQ>,
+    // Set if loading stopped at an error or skipped lines, so we only have some of the json
+    pub incomplete: bool,
+    // Set if the input was compressed, going by its contents rather than its name
+    pub compressed: bool,
 }
 
 pub struct Flash {
@@ -51,20 +70,93 @@ impl Focus {
 
 impl App {
     pub fn new<R: io::Read>(r: R, name: String, layout: JexLayout) -> io::Result<Self> {
-        let views = ViewTree::new_from_reader(r, name, layout)?;
+        let values = Deserializer::from_reader(r)
+            .into_iter::<JV>()
+            .collect::<Result<Vec<JV>, _>>()?;
+        Ok(App::with_views(ViewTree::new(values, name, layout)))
+    }
+    // Like new, but the json is parsed in the background. Call poll_loader to pick up new values.
+    pub fn new_loading<R: io::Read + Send + 'static>(
+        r: R,
+        name: String,
+        format: Format,
+        total_bytes: Option<u64>,
+        layout: JexLayout,
+    ) -> Self {
+        let (views, loader) = ViewTree::new_from_reader(r, name, format, total_bytes, layout);
+        let mut app = App::with_views(views);
+        app.loader = Some(loader);
+        app
+    }
+    fn with_views(views: ViewTree) -> Self {
         let index = ViewTreeIndex {
             parent: Vec::new(),
             child: 0,
         };
-        let app = App {
+        App {
             views,
             index,
             focus: Focus::Left,
-            search_re: None,
+            search: None,
+            search_matches: None,
+            search_count: None,
             show_tree: false,
             flash: None,
+            loader: None,
+            programs: HashMap::new(),
+            incomplete: false,
+            compressed: false,
+        }
+    }
+    // Adds any newly loaded values to the root view. Returns whether anything changed.
+    pub fn poll_loader(&mut self, layout: JexLayout) -> bool {
+        let loader = match &mut self.loader {
+            Some(loader) => loader,
+            None => return false,
         };
-        Ok(app)
+        let update = loader.poll();
+        self.compressed |= update.compressed;
+        let changed = !update.values.is_empty() || update.error.is_some() || update.done;
+        if !update.values.is_empty() {
+            self.views
+                .append_loaded(update.values, layout, &mut self.programs);
+        }
+        if let Some(err) = update.error {
+            let loaded = match &self.views.view_frame.view {
+                View::Json(Some(view)) => view.values.len(),
+                View::Json(None) | View::Error(_) => 0,
+            };
+            self.incomplete = true;
+            let text = load_error_text(&err, loaded);
+            if loaded == 0 {
+                // Otherwise there'd be nothing but an empty pane once the popup's closed
+                let lines = text.lines.iter().map(|spans| {
+                    spans
+                        .0
+                        .iter()
+                        .map(|span| span.content.as_ref())
+                        .collect::<String>()
+                });
+                self.views.view_frame.view = View::Error(lines.collect());
+                self.views.rerun_descendants(layout.right);
+            }
+            self.set_flash_text(text);
+        }
+        if !update.skipped.is_empty() {
+            self.incomplete = true;
+            let heading = match update.skipped.len() {
+                1 => "Skipped 1 line that didn't parse:".to_string(),
+                n => format!("Skipped {} lines that didn't parse:", n),
+            };
+            self.set_flash(format!("{}\n{}", heading, update.skipped.join("\n")));
+        }
+        if update.done {
+            self.loader = None;
+            self.programs.clear();
+            // Diffing every batch would redo the whole diff each time, so it waits for the rest
+            self.refresh_diff();
+        }
+        changed
     }
     fn current_views(&self) -> (&ViewFrame, &ViewFrame, &String) {
         self.views
@@ -96,11 +188,98 @@ impl App {
         match &mut left.view {
             View::Json(Some(left)) => {
                 right.view = left.apply_query(query, right_rect);
+                right.apply_fold_rules(0);
             }
             View::Json(None) | View::Error(_) => {
                 right.view = View::Json(None);
             }
         }
+        self.refresh_diff();
+    }
+    pub fn is_diffing(&self) -> bool {
+        let (left, _, _) = self.current_views();
+        matches!(&left.view, View::Json(Some(view)) if view.diff.is_some())
+    }
+    // Compares the left and right panes, or stops comparing them if they already are.
+    pub fn toggle_diff(&mut self) -> Result<(), String> {
+        if self.is_diffing() {
+            let (left, right, _) = self.current_views_mut();
+            for frame in [left, right].iter_mut() {
+                if let View::Json(Some(view)) = &mut frame.view {
+                    view.clear_diff();
+                }
+            }
+            return Ok(());
+        }
+        self.start_diff()
+    }
+    // Marks the differences between the panes, moving the cursors to the top and folding away
+    // everything that's the same
+    fn start_diff(&mut self) -> Result<(), String> {
+        let (left, right, _) = self.current_views_mut();
+        match (&mut left.view, &mut right.view) {
+            (View::Json(Some(left)), View::Json(Some(right))) => {
+                let diff = Diff::new(&left.values, &right.values);
+                let identical = diff.is_empty();
+                left.set_diff(diff.left, diff.left_folds);
+                right.set_diff(diff.right, diff.right_folds);
+                if identical {
+                    self.set_flash("No differences".to_string());
+                }
+                Ok(())
+            }
+            _ => Err("Both panes need json to compare".to_string()),
+        }
+    }
+    // Shows other json in the right pane (until the query is rerun), compared against the left.
+    pub fn compare_with(&mut self, values: Vec<JV>, name: String, right_rect: Rect) {
+        let (_, right, _) = self.current_views_mut();
+        right.view = View::new(values, right_rect);
+        right.name = name;
+        if let Err(err) = self.start_diff() {
+            self.set_flash(err);
+        }
+    }
+    // Diffs go stale when either side changes, so they're recomputed from scratch. Only the marks
+    // change, so the cursors and folds stay where they were.
+    fn refresh_diff(&mut self) {
+        if !self.is_diffing() {
+            return;
+        }
+        let (left, right, _) = self.current_views_mut();
+        match (&mut left.view, &mut right.view) {
+            (View::Json(Some(left)), View::Json(Some(right))) => {
+                let diff = Diff::new(&left.values, &right.values);
+                left.update_diff(diff.left);
+                right.update_diff(diff.right);
+            }
+            (View::Json(Some(left)), _) => {
+                left.clear_diff();
+            }
+            _ => {}
+        }
+    }
+    // Edits only happen in the left pane: the right pane is always the result of a query.
+    pub fn left_cursor(&self) -> Option<&LeafCursor> {
+        let (left, _, _) = self.current_views();
+        match &left.view {
+            View::Json(Some(view)) => Some(&view.cursor),
+            View::Json(None) | View::Error(_) => None,
+        }
+    }
+    // Applies the edit to the left pane's json, and reruns every query that depends on it.
+    pub fn edit_left(&mut self, edit: Edit, right_rect: Rect) -> Result<(), String> {
+        let tree = self
+            .views
+            .index_tree_mut(&self.index.parent)
+            .expect("App index invalidated");
+        match &mut tree.view_frame.view {
+            View::Json(Some(view)) => view.apply_edit(edit)?,
+            View::Json(None) | View::Error(_) => return Err("Nothing to edit".to_string()),
+        }
+        tree.rerun_descendants(right_rect);
+        self.refresh_diff();
+        Ok(())
     }
     pub fn render<B: tui::backend::Backend>(
         &self,
@@ -134,12 +313,31 @@ impl App {
                     tree_rect,
                 );
             }
+            if let View::Json(Some(view)) = &self.focused_view().view {
+                let mut status = view.cursor.status();
+                if let Some((index, focus, count)) = &self.search_count {
+                    if *index == self.index
+                        && focus == &self.focus
+                        && view.cursor.matches_path(&count.path)
+                    {
+                        status.push_str(&format!("  {}", count));
+                    }
+                }
+                let status =
+                    Paragraph::new(status).style(Style::default().add_modifier(Modifier::REVERSED));
+                f.render_widget(status, layout.status);
+            }
             match mode {
                 AppRenderMode::Normal => {
                     let query = Paragraph::new(query.as_str())
                         .alignment(Alignment::Left)
                         .wrap(Wrap { trim: false });
                     f.render_widget(query, layout.query);
+                    if let Some(loader) = self.loader.as_ref() {
+                        let progress =
+                            Paragraph::new(loader.progress()).alignment(Alignment::Right);
+                        f.render_widget(progress, layout.query);
+                    }
                 }
                 AppRenderMode::InputEditor => {
                     f.set_cursor(0, layout.query.y);
@@ -162,9 +360,15 @@ impl App {
             }
         }
     }
+    // Starts a new search, and jumps to its first match
+    pub fn set_search(&mut self, search: Search) {
+        self.search = Some(search);
+        self.search_matches = None;
+        self.search(false);
+    }
     pub fn search(&mut self, reverse: bool) {
-        let re = if let Some(re) = &self.search_re {
-            re
+        let search = if let Some(search) = &self.search {
+            search
         } else {
             return;
         };
@@ -182,22 +386,77 @@ impl App {
             return;
         };
         let search_hit = if reverse {
-            view.cursor.clone().search_back(re)
+            view.cursor
+                .clone()
+                .search_back_by(|cursor| search.matches(cursor))
         } else {
-            view.cursor.clone().search(re)
+            view.cursor
+                .clone()
+                .search_by(|cursor| search.matches(cursor))
         };
         if let Some(search_hit) = search_hit {
             view.cursor = search_hit;
-        } else {
-            return;
+            view.unfold_around_cursor();
+            if !view
+                .visible_range(&view.folds)
+                .contains_value(&view.cursor.to_path())
+            {
+                view.scroll = GlobalCursor::new(view.values.clone(), view.rect.width, &view.folds)
+                    .expect("values should still exist");
+            }
+        }
+        let up_to_date = match &self.search_matches {
+            Some((index, focus, matches)) => {
+                *index == self.index
+                    && *focus == self.focus
+                    && Rc::ptr_eq(&matches.jsons, &view.values)
+            }
+            None => false,
         };
-        view.unfold_around_cursor();
-        if !view
-            .visible_range(&view.folds)
-            .contains_value(&view.cursor.to_path())
-        {
-            view.scroll = GlobalCursor::new(view.values.clone(), view.rect.width, &view.folds)
-                .expect("values should still exist");
+        if !up_to_date {
+            let matches = search.find_all(view.values.clone());
+            self.search_matches = Some((self.index.clone(), self.focus, matches));
+        }
+        if let Some((_, _, matches)) = &self.search_matches {
+            let count = matches.count(&view.cursor);
+            self.search_count = Some((self.index.clone(), self.focus, count));
+        }
+    }
+    // Clicking a view tree entry selects it, and clicking a pane focuses it and moves its cursor.
+    pub fn click(&mut self, column: u16, row: u16, layout: JexLayout) {
+        if let Some(tree_rect) = layout.tree {
+            let inner = Block::default().borders(Borders::ALL).inner(tree_rect);
+            if contains(inner, column, row) {
+                if let Some(index) = self.views.index_at_row((row - inner.y) as usize) {
+                    self.index = index;
+                }
+                return;
+            }
+        }
+        if let Some((focus, rect)) = pane_at(column, row, layout) {
+            self.focus = focus;
+            if let View::Json(Some(view)) = &mut self.focused_view_mut().view {
+                view.click(column - rect.x, row - rect.y);
+            }
+        }
+    }
+    // Scrolls whichever pane the mouse is over, without focusing it.
+    pub fn scroll(&mut self, column: u16, row: u16, down: bool, lines: usize, layout: JexLayout) {
+        let focus = match pane_at(column, row, layout) {
+            Some((focus, _)) => focus,
+            None => return,
+        };
+        let (left, right, _) = self.current_views_mut();
+        let frame = match focus {
+            Focus::Left => left,
+            Focus::Right => right,
+        };
+        if let View::Json(Some(view)) = &mut frame.view {
+            if down {
+                view.scroll_down(lines);
+            } else {
+                view.scroll_up(lines);
+            }
         }
     }
     pub fn resize(&mut self, layout: JexLayout) {
@@ -207,8 +466,11 @@ impl App {
         right.view.resize_to(layout.right);
     }
     pub fn set_flash(&mut self, s: String) {
+        self.set_flash_text(Text::from(s));
+    }
+    pub fn set_flash_text(&mut self, text: Text<'static>) {
         self.flash = Some(Flash {
-            paragraph: Paragraph::new(Text::from(s)).wrap(Wrap { trim: false }),
+            paragraph: Paragraph::new(text).wrap(Wrap { trim: false }),
             scroll: 0,
         });
     }
@@ -223,3 +485,70 @@ impl App {
         self.set_flash(controls.to_string());
     }
 }
+
+// What went wrong, the lines leading up to it (with the error highlighted), and how much loaded
+fn load_error_text(err: &LoadError, loaded: usize) -> Text<'static> {
+    let mut lines = vec![Spans::from(match err.location {
+        Some(Location { line, column }) => {
+            format!("Error loading json at line {}, column {}:", line, column)
+        }
+        None => "Error loading json:".to_string(),
+    })];
+    lines.extend(
+        err.message
+            .split('\n')
+            .map(|line| Spans::from(line.to_owned())),
+    );
+    if let Some((last_number, _)) = err.excerpt.last() {
+        lines.push(Spans::default());
+        let width = last_number.to_string().len();
+        for (i, (number, line)) in err.excerpt.iter().enumerate() {
+            let mut spans = vec![Span::styled(
+                format!("{:>width$} | ", number, width = width),
+                Style::default().fg(Color::DarkGray),
+            )];
+            if i + 1 == err.excerpt.len() {
+                let mut chars = line.chars();
+                let before: String = chars
+                    .by_ref()
+                    .take(err.excerpt_column.saturating_sub(1))
+                    .collect();
+                // Errors at the end of a line point just past it
+                let at = chars.next().map_or(" ".to_string(), String::from);
+                spans.push(Span::raw(before));
+                spans.push(Span::styled(
+                    at,
+                    Style::default().fg(Color::White).bg(Color::Red),
+                ));
+                spans.push(Span::raw(chars.collect::<String>()));
+            } else {
+                spans.push(Span::raw(line.clone()));
+            }
+            lines.push(Spans::from(spans));
+        }
+    }
+    lines.push(Spans::default());
+    lines.push(Spans::from(match loaded {
+        0 => "Nothing loaded before the error.".to_string(),
+        1 => "Showing the 1 value that loaded before the error.".to_string(),
+        n => format!("Showing the {} values that loaded before the error.", n),
+    }));
+    Text::from(lines)
+}
+
+// The pane at this position, and the area its json is drawn in
+fn pane_at(column: u16, row: u16, layout: JexLayout) -> Option<(Focus, Rect)> {
+    let panes = [(Focus::Left, layout.left), (Focus::Right, layout.right)];
+    panes.iter().find_map(|&(focus, rect)| {
+        let inner = Block::default().borders(Borders::ALL).inner(rect);
+        if contains(inner, column, row) {
+            Some((focus, inner))
+        } else {
+            None
+        }
+    })
+}
+
+fn contains(rect: Rect, column: u16, row: u16) -> bool {
+    (rect.x..rect.x + rect.width).contains(&column) && (rect.y..rect.y + rect.height).contains(&row)
+}
@@ -54,6 +54,31 @@ fn bench_load_indirect(c: &mut Criterion) {
     });
 }
 
+fn bench_load_numbers(c: &mut Criterion) {
+    c.bench_function("bench_load_numbers", |bench| {
+        // Mostly numbers that doubles hold exactly, along with ids that they don't
+        let s = (0..10_000u64)
+            .map(|i| {
+                format!(
+                    r#"{{"id": {}, "count": {}, "price": {}, "ratio": {}}}"#,
+                    1_234_567_890_123_456_789 + i,
+                    i,
+                    i as f64 / 100.0,
+                    i as f64 / 7.0
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        bench.iter(|| {
+            let content: Vec<JV> = Deserializer::from_str(&s)
+                .into_iter::<JV>()
+                .collect::<Result<Vec<JV>, _>>()
+                .expect("serde deserialization error");
+            content
+        })
+    });
+}
+
 fn bench_load_native(c: &mut Criterion) {
     c.bench_function("bench_load_native", |bench| {
         let s = fs::read_to_string("testdata/example.json").expect("cannot read file");
@@ -130,6 +155,7 @@ criterion_group!(
         bench_jq_roundtrip,
         bench_load_direct,
         bench_load_indirect,
+        bench_load_numbers,
         bench_load_native,
         bench_scroll_long_string,
         bench_render_long_string,
@@ -0,0 +1,114 @@
+use crate::{cursor::LeafCursor, jq::jv::JV};
+use std::{
+    fs,
+    io::{self, Write},
+    process::{Command, Output, Stdio},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyKind {
+    // The value as (pretty printed) json
+    Json,
+    // The jq path to the value
+    Path,
+    // Like Json, but strings are copied without quotes or escapes (like jq -r)
+    Raw,
+}
+
+// Where copied text goes
+#[derive(Debug, Clone)]
+pub enum Clipboard {
+    // The OSC 52 escape sequence asks the terminal to set the clipboard. It works over ssh and
+    // doesn't need a clipboard daemon, but not every terminal supports it.
+    Osc52,
+    // A shell command that's given the text on stdin (eg, `xclip -selection clipboard`)
+    Command(String),
+    File(String),
+}
+
+impl Clipboard {
+    pub fn copy(&self, text: &str) -> Result<(), String> {
+        match self {
+            Clipboard::Osc52 => {
+                let mut stdout = io::stdout();
+                write!(stdout, "\x1b]52;c;{}\x07", base64::encode(text))
+                    .and_then(|()| stdout.flush())
+                    .map_err(|err| format!("Error copying:\n{}", err))
+            }
+            Clipboard::Command(command) => {
+                let output = run_copy_command(command, text)
+                    .map_err(|err| format!("Error running {}:\n{}", command, err))?;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    return Err(format!("Error running {}:\n{}", command, stderr.trim()));
+                }
+                Ok(())
+            }
+            Clipboard::File(path) => {
+                fs::write(path, text).map_err(|err| format!("Error copying to {}:\n{}", path, err))
+            }
+        }
+    }
+}
+
+fn run_copy_command(command: &str, text: &str) -> io::Result<Output> {
+    // Anything the command prints would end up on top of the UI
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("Child should have stdin")
+        .write_all(text.as_bytes())?;
+    child.wait_with_output()
+}
+
+pub fn copy_text(cursor: &LeafCursor, kind: CopyKind) -> String {
+    match (kind, &cursor.focus) {
+        (CopyKind::Path, _) => cursor.jq_path(),
+        (CopyKind::Raw, JV::String(s)) => s.value().to_owned(),
+        (CopyKind::Json, json) | (CopyKind::Raw, json) => {
+            serde_json::to_string_pretty(json).expect("Values should serialize")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{copy_text, Clipboard, CopyKind};
+    use crate::{cursor::LeafCursor, jq::jv::JV};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::{collections::HashSet, fs};
+    #[test]
+    fn unit_copy_text() {
+        let json: JV = (&json!({"name": "a \"b\"", "n": [1.0]})).into();
+        let cursor_at = |path: &str| {
+            let mut cursor = LeafCursor::new(vec![json.clone()].into()).unwrap();
+            while cursor.jq_path() != path {
+                cursor.advance(&HashSet::new()).unwrap();
+            }
+            cursor
+        };
+        let cursor = cursor_at(".name");
+        assert_eq!(copy_text(&cursor, CopyKind::Json), r#""a \"b\"""#);
+        assert_eq!(copy_text(&cursor, CopyKind::Raw), r#"a "b""#);
+        assert_eq!(copy_text(&cursor, CopyKind::Path), ".name");
+        let cursor = cursor_at(".n");
+        assert_eq!(copy_text(&cursor, CopyKind::Raw), "[\n  1\n]");
+    }
+    #[test]
+    fn unit_copy_command() {
+        let path = std::env::temp_dir().join(format!("jex-copy-{}", std::process::id()));
+        let command = format!("cat > '{}'", path.display());
+        Clipboard::Command(command).copy("copied").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "copied");
+        fs::remove_file(&path).unwrap();
+        assert!(Clipboard::Command("exit 1".to_string()).copy("x").is_err());
+    }
+}
@@ -1,10 +1,11 @@
 use crate::{
+    diff::{mark_at, DiffMarks},
     jq::jv::{JVArray, JVObject, JVString, OwnedObjectIterator, JV},
     lines::{Leaf, LeafContent, LineCursor, UnstyledSpans},
 };
 use log::trace;
-use regex::Regex;
-use std::{borrow::Cow, cmp::Ordering, collections::HashSet, fmt, rc::Rc};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, cmp::Ordering, collections::HashSet, convert::TryFrom, fmt, rc::Rc};
 use tui::{layout::Rect, text::Spans};
 
 // Requirements:
@@ -13,7 +14,7 @@ use tui::{layout::Rect, text::Spans};
 // * (Optionally, for searching): Step backwards
 // * Can be "dehydrated" into something hashable for storing folds (other metadata?)
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum FocusPosition {
     Start,
     Value,
@@ -310,12 +311,13 @@ impl GlobalCursor {
         &mut self,
         cursor: Option<&LeafCursor>,
         folds: &HashSet<(usize, Vec<usize>)>,
+        marks: Option<&DiffMarks>,
         rect: Rect,
     ) -> Vec<Spans<'static>> {
         let mut lines = Vec::with_capacity(rect.height as usize);
         self.resize_to(rect);
         lines.push(
-            self.current_line()
+            self.current_marked_line(marks)
                 .to_spans(Some(&self.value_cursor) == cursor),
         );
         while lines.len() < rect.height as usize {
@@ -323,12 +325,34 @@ impl GlobalCursor {
                 break;
             };
             lines.push(
-                self.current_line()
+                self.current_marked_line(marks)
                     .to_spans(Some(&self.value_cursor) == cursor),
             );
         }
         lines
     }
+    // The cursor for the line render_lines would draw on this row, for mapping clicks back to json.
+    pub fn at_row(
+        &self,
+        row: u16,
+        folds: &HashSet<(usize, Vec<usize>)>,
+        width: u16,
+    ) -> Option<GlobalCursor> {
+        let mut cursor = self.clone();
+        for _ in 0..row {
+            cursor.advance(folds, width)?;
+        }
+        Some(cursor)
+    }
+    fn current_marked_line(&self, marks: Option<&DiffMarks>) -> UnstyledSpans {
+        let mut line = self.current_line();
+        if let Some(marks) = marks {
+            if let Some(kind) = mark_at(marks, &self.value_cursor.to_path().strip_position()) {
+                line.mark(kind);
+            }
+        }
+        line
+    }
     pub fn advance(&mut self, folds: &HashSet<(usize, Vec<usize>)>, width: u16) -> Option<()> {
         trace!("Advancing global cursor (width={}): {:#?}", width, self);
         let lc = &mut self.line_cursor;
@@ -426,23 +450,24 @@ impl LeafCursor {
         }
     }
     pub fn from_path(jsons: Rc<[JV]>, path: &ValuePath) -> Self {
-        let mut focus = jsons[path.top_index].clone();
+        Self::try_from_path(jsons, path).expect("Shape of path does not match shape of jsons")
+    }
+    // Like from_path, but for paths that might not come from these jsons (eg, from a saved
+    // session).
+    pub fn try_from_path(jsons: Rc<[JV]>, path: &ValuePath) -> Option<Self> {
+        let mut focus = jsons.get(path.top_index)?.clone();
         let mut frames = Vec::new();
         for &index in path.frames.iter() {
             match focus {
                 JV::Array(arr) => {
                     let json = arr.clone();
-                    focus = arr
-                        .get(index as i32)
-                        .expect("Shape of path does not match shape of jsons");
+                    focus = arr.get(index as i32)?;
                     frames.push(CursorFrame::Array { index, json });
                 }
                 JV::Object(obj) => {
                     let json = obj.clone();
                     let mut iterator = obj.clone().into_iter();
-                    let (key, new_focus) = iterator
-                        .nth(index)
-                        .expect("Shape of path does not match shape of jsons");
+                    let (key, new_focus) = iterator.nth(index)?;
                     focus = new_focus;
                     frames.push(CursorFrame::Object {
                         index,
@@ -451,16 +476,73 @@ impl LeafCursor {
                         iterator,
                     });
                 }
-                _ => panic!("Shape of path does not match shape of jsons"),
+                _ => return None,
             }
         }
-        LeafCursor {
+        let position_matches = match (&focus, path.focus_position) {
+            (JV::Array(_), FocusPosition::Value) | (JV::Object(_), FocusPosition::Value) => false,
+            (JV::Array(_), _) | (JV::Object(_), _) => true,
+            (_, position) => position == FocusPosition::Value,
+        };
+        if !position_matches {
+            return None;
+        }
+        Some(LeafCursor {
             jsons,
             top_index: path.top_index,
             frames,
             focus,
             focus_position: path.focus_position,
+        })
+    }
+    // Focuses on the first line of the value at the path, whether or not it's a container.
+    pub fn try_from_stripped_path(jsons: Rc<[JV]>, stripped: (usize, Vec<usize>)) -> Option<Self> {
+        let mut path = ValuePath::from_stripped(stripped);
+        if let Some(cursor) = Self::try_from_path(jsons.clone(), &path) {
+            return Some(cursor);
         }
+        path.focus_position = FocusPosition::Value;
+        Self::try_from_path(jsons, &path)
+    }
+    // Structural moves, for getting past big values without going through every line of them. Each
+    // one lands on the first line of its target, or returns None if there's nowhere to go.
+    pub fn parent(&self) -> Option<Self> {
+        let (top_index, mut frames) = self.to_path().strip_position();
+        frames.pop()?;
+        Self::try_from_stripped_path(self.jsons.clone(), (top_index, frames))
+    }
+    pub fn next_sibling(&self) -> Option<Self> {
+        self.sibling(true)
+    }
+    pub fn prev_sibling(&self) -> Option<Self> {
+        self.sibling(false)
+    }
+    // Top level values count as siblings of each other
+    fn sibling(&self, forward: bool) -> Option<Self> {
+        let (mut top_index, mut frames) = self.to_path().strip_position();
+        let index = frames.last_mut().unwrap_or(&mut top_index);
+        *index = if forward {
+            *index + 1
+        } else {
+            index.checked_sub(1)?
+        };
+        Self::try_from_stripped_path(self.jsons.clone(), (top_index, frames))
+    }
+    pub fn first_child(&self) -> Option<Self> {
+        self.child(false)
+    }
+    pub fn last_child(&self) -> Option<Self> {
+        self.child(true)
+    }
+    fn child(&self, last: bool) -> Option<Self> {
+        let len = match &self.focus {
+            JV::Array(arr) => arr.len(),
+            JV::Object(obj) => obj.len(),
+            _ => return None,
+        } as usize;
+        let (top_index, mut frames) = self.to_path().strip_position();
+        frames.push(if last { len.checked_sub(1)? } else { 0 });
+        Self::try_from_stripped_path(self.jsons.clone(), (top_index, frames))
     }
     pub fn current_key(&self) -> Option<JVString> {
         match self.focus_position {
@@ -488,7 +570,7 @@ impl LeafCursor {
             (JV::Array(arr), Start, true) => LeafContent::FoldedArray(arr.len() as usize),
             (JV::Null(_), Value, _) => LeafContent::Null,
             (JV::Bool(b), Value, _) => LeafContent::Bool(b.value()),
-            (JV::Number(x), Value, _) => LeafContent::Number(x.value()),
+            (JV::Number(x), Value, _) => LeafContent::Number(x.clone()),
             (JV::String(s), Value, _) => LeafContent::String(s.clone()),
             triple => panic!("Illegal json/focus_position/folded triple: {:?}", triple),
         };
@@ -583,43 +665,76 @@ impl LeafCursor {
         }
         Some(())
     }
-    fn leaf_to_string(&self) -> Option<Cow<str>> {
+    pub fn leaf_to_string(&self) -> Option<Cow<'_, str>> {
         match &self.focus {
             JV::Null(_) => Some("null".into()),
             JV::Bool(b) => Some(b.value().to_string().into()),
-            JV::Number(x) => Some(x.value().to_string().into()),
+            JV::Number(x) => Some(x.to_string().into()),
             JV::String(s) => Some(s.value().into()),
             _ => None,
         }
     }
-    // TODO: do something more efficient
-    pub fn matches_path(&self, path: &ValuePath) -> bool {
-        self.to_path() == *path
-    }
-    pub fn regex_matches(&self, re: &Regex) -> bool {
-        if let Some(leaf) = self.leaf_to_string() {
-            if re.is_match(&leaf) {
-                return true;
+    // The path to the focus as a jq query (eg, `.items[3].name`), relative to its top level value.
+    pub fn jq_path(&self) -> String {
+        let mut out = String::new();
+        for frame in self.frames.iter() {
+            match frame {
+                CursorFrame::Array { index, .. } => out.push_str(&format!("[{}]", index)),
+                CursorFrame::Object { key, .. } if is_identifier(key.value()) => {
+                    out.push('.');
+                    out.push_str(key.value());
+                }
+                CursorFrame::Object { key, .. } => {
+                    let quoted =
+                        serde_json::to_string(key.value()).expect("Strings should serialize");
+                    out.push_str(&format!("[{}]", quoted));
+                }
             }
         }
-        if let Some(CursorFrame::Object { key, .. }) = self.frames.last() {
-            if re.is_match(key.value()) {
-                return true;
-            }
+        if !out.starts_with('.') {
+            out.insert(0, '.');
         }
-        false
+        out
     }
-    pub fn search(mut self, re: &Regex) -> Option<Self> {
+    // A summary of where the cursor is, for the status bar: the jq path, the focus's type (and size,
+    // for containers), and which top level value it's in.
+    pub fn status(&self) -> String {
+        let kind = match &self.focus {
+            JV::Array(arr) => match arr.len() {
+                1 => "array (1 element)".to_string(),
+                n => format!("array ({} elements)", n),
+            },
+            JV::Object(obj) => match obj.len() {
+                1 => "object (1 key)".to_string(),
+                n => format!("object ({} keys)", n),
+            },
+            json => type_name(json).to_string(),
+        };
+        format!(
+            "{}  {}  value {}/{}",
+            self.jq_path(),
+            kind,
+            self.top_index + 1,
+            self.jsons.len()
+        )
+    }
+    // TODO: do something more efficient
+    pub fn matches_path(&self, path: &ValuePath) -> bool {
+        self.to_path() == *path
+    }
+    // Finds the next value (wrapping around to the start) that the predicate accepts, ignoring
+    // folds.
+    pub fn search_by<F: Fn(&Self) -> bool>(mut self, matches: F) -> Option<Self> {
         let mock_folds = HashSet::new();
         let start = self.to_path();
         while let Some(()) = self.advance(&mock_folds) {
-            if self.regex_matches(re) {
+            if matches(&self) {
                 return Some(self);
             }
         }
         let mut cursor = LeafCursor::new(self.jsons).expect("Jsons can't be empty here");
         while !cursor.matches_path(&start) {
-            if cursor.regex_matches(re) {
+            if matches(&cursor) {
                 return Some(cursor);
             }
             cursor
@@ -628,17 +743,17 @@ impl LeafCursor {
         }
         None
     }
-    pub fn search_back(mut self, re: &Regex) -> Option<Self> {
+    pub fn search_back_by<F: Fn(&Self) -> bool>(mut self, matches: F) -> Option<Self> {
         let mock_folds = HashSet::new();
         let start = self.to_path();
         while let Some(()) = self.regress(&mock_folds) {
-            if self.regex_matches(re) {
+            if matches(&self) {
                 return Some(self);
             }
         }
         let mut cursor = LeafCursor::new_end(self.jsons).expect("Jsons can't be empty here");
         while !cursor.matches_path(&start) {
-            if cursor.regex_matches(re) {
+            if matches(&cursor) {
                 return Some(cursor);
             }
             cursor
@@ -661,13 +776,64 @@ impl LeafCursor {
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Serialize, Deserialize)]
 pub struct ValuePath {
     top_index: usize,
     frames: Vec<usize>,
     focus_position: FocusPosition,
 }
 impl ValuePath {
+    // Resolves a path like `.items[3].name` (the kind jq_path produces, plus negative indices)
+    // against the top level value at top_index.
+    pub fn from_jq_path(jsons: &[JV], top_index: usize, path: &str) -> Result<Self, String> {
+        Self::from_segments(jsons, top_index, parse_jq_path(path)?)
+    }
+    pub fn from_segments(
+        jsons: &[JV],
+        top_index: usize,
+        segments: Vec<PathSegment>,
+    ) -> Result<Self, String> {
+        let mut focus = jsons
+            .get(top_index)
+            .ok_or_else(|| "There's no value to go to".to_string())?
+            .clone();
+        let mut frames = Vec::new();
+        for segment in segments {
+            let (index, child) = match (&segment, &focus) {
+                (PathSegment::Index(i), JV::Array(arr)) => {
+                    let i = if *i < 0 { i + arr.len() as i64 } else { *i };
+                    let child = usize::try_from(i)
+                        .ok()
+                        .and_then(|i| Some((i, arr.get(i32::try_from(i).ok()?)?)));
+                    child.ok_or_else(|| format!("{} is out of range", segment))?
+                }
+                (PathSegment::Key(key), JV::Object(obj)) => obj
+                    .clone()
+                    .into_iter()
+                    .enumerate()
+                    .find(|(_, (k, _))| k.value() == key)
+                    .map(|(i, (_, v))| (i, v))
+                    .ok_or_else(|| format!("There's no key {}", segment))?,
+                (_, json) => {
+                    return Err(format!("Cannot index {} with {}", type_name(json), segment))
+                }
+            };
+            frames.push(index);
+            focus = child;
+        }
+        Ok(ValuePath {
+            top_index,
+            frames,
+            focus_position: FocusPosition::starting(&focus),
+        })
+    }
+    pub fn from_stripped((top_index, frames): (usize, Vec<usize>)) -> Self {
+        ValuePath {
+            top_index,
+            frames,
+            focus_position: FocusPosition::Start,
+        }
+    }
     pub fn strip_position(self) -> (usize, Vec<usize>) {
         let ValuePath {
             top_index,
@@ -723,9 +889,115 @@ pub struct GlobalPath {
     pub current_line: usize,
 }
 
+// What jq's `type` calls the value
+fn type_name(json: &JV) -> &'static str {
+    match json {
+        JV::Null(_) => "null",
+        JV::Bool(_) => "boolean",
+        JV::Number(_) => "number",
+        JV::String(_) => "string",
+        JV::Array(_) => "array",
+        JV::Object(_) => "object",
+    }
+}
+
+// One step of a jq path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(i64),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathSegment::Key(key) => write!(
+                f,
+                "{}",
+                serde_json::to_string(key).expect("Strings should serialize")
+            ),
+            PathSegment::Index(i) => write!(f, "[{}]", i),
+        }
+    }
+}
+
+// Accepts `.key`, `."key"`, `["key"]` and `[N]` steps, optionally with a dot before a bracket
+fn parse_jq_path(path: &str) -> Result<Vec<PathSegment>, String> {
+    let path = path.trim();
+    if !path.starts_with('.') {
+        return Err(format!("Paths start with a '.': {}", path));
+    }
+    let mut segments = Vec::new();
+    let mut rest = path;
+    while !rest.is_empty() {
+        let (segment, after) = if let Some(after) = rest.strip_prefix('[') {
+            let (segment, after) = parse_bracket(after)?;
+            (Some(segment), after)
+        } else if let Some(after) = rest.strip_prefix('.') {
+            if after.starts_with('"') {
+                let (key, after) = parse_string(after)?;
+                (Some(PathSegment::Key(key)), after)
+            } else {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                let (key, after) = after.split_at(end);
+                // A lone dot is fine at the start, or before a bracket
+                if key.is_empty() && !(rest.len() == path.len() || after.starts_with('[')) {
+                    return Err(format!("Expected a key after the '.' in {}", path));
+                }
+                let segment = Some(PathSegment::Key(key.to_string())).filter(|_| !key.is_empty());
+                (segment, after)
+            }
+        } else {
+            return Err(format!("Expected '.' or '[' at {}", rest));
+        };
+        segments.extend(segment);
+        rest = after.trim_start();
+    }
+    Ok(segments)
+}
+
+fn parse_bracket(s: &str) -> Result<(PathSegment, &str), String> {
+    let s = s.trim_start();
+    let (segment, after) = if s.starts_with('"') {
+        let (key, after) = parse_string(s)?;
+        (PathSegment::Key(key), after)
+    } else {
+        let end = s.find(']').unwrap_or(s.len());
+        let i = s[..end]
+            .trim()
+            .parse()
+            .map_err(|_| format!("Expected an index or a string key, not {}", &s[..end]))?;
+        (PathSegment::Index(i), &s[end..])
+    };
+    match after.trim_start().strip_prefix(']') {
+        Some(after) => Ok((segment, after)),
+        None => Err("Missing a closing ']'".to_string()),
+    }
+}
+
+fn parse_string(s: &str) -> Result<(String, &str), String> {
+    let mut stream = serde_json::Deserializer::from_str(s).into_iter::<String>();
+    match stream.next() {
+        Some(Ok(key)) => Ok((key, &s[stream.byte_offset()..])),
+        _ => Err(format!("Invalid string in path: {}", s)),
+    }
+}
+
+// Keys that jq lets you write as `.key`, rather than `.["key"]`
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{GlobalCursor, LeafCursor};
+    use super::{FocusPosition, GlobalCursor, LeafCursor, ValuePath};
     use crate::{
         jq::jv::JV,
         lines::LineCursor,
@@ -736,6 +1008,106 @@ mod tests {
     use serde_json::{json, Value};
     use std::{collections::HashSet, rc::Rc};
 
+    #[test]
+    fn unit_jq_path() {
+        let json: JV = (&json!({"items": [1.0, {"a b": null, "_x1": true}]})).into();
+        let mut cursor = LeafCursor::new(vec![json].into()).unwrap();
+        let mut paths = vec![cursor.jq_path()];
+        while let Some(()) = cursor.advance(&HashSet::new()) {
+            paths.push(cursor.jq_path());
+        }
+        paths.dedup();
+        let expected = vec![
+            ".",
+            ".items",
+            ".items[0]",
+            ".items[1]",
+            ".items[1]._x1",
+            ".items[1][\"a b\"]",
+            ".items[1]",
+            ".items",
+            ".",
+        ];
+        assert_eq!(paths, expected);
+    }
+    #[test]
+    fn unit_from_jq_path() {
+        let json: JV = (&json!({"items": [1.0, {"a b": null, "_x1": true}]})).into();
+        let jsons: Vec<JV> = vec![json.clone(), json];
+        let mut cursor = LeafCursor::new(jsons.clone().into()).unwrap();
+        cursor.top_index = 1;
+        // Every path jq_path makes leads back to the same place
+        while let Some(()) = cursor.advance(&HashSet::new()) {
+            let path = ValuePath::from_jq_path(&jsons, 1, &cursor.jq_path()).unwrap();
+            let found = LeafCursor::from_path(jsons.clone().into(), &path);
+            assert_eq!(found.jq_path(), cursor.jq_path());
+        }
+        let path = |s| ValuePath::from_jq_path(&jsons, 0, s).map(|path| path.strip_position());
+        assert_eq!(path(" .items[-1] .\"a b\""), Ok((0, vec![0, 1, 1])));
+        assert_eq!(path(".items.[0]"), Ok((0, vec![0, 0])));
+        assert_eq!(path(".[\"items\"][ 1 ]"), Ok((0, vec![0, 1])));
+        assert_eq!(path(".items[2]"), Err("[2] is out of range".to_string()));
+        assert_eq!(
+            path(".items.x"),
+            Err("Cannot index array with \"x\"".to_string())
+        );
+        assert_eq!(path(".nope"), Err("There's no key \"nope\"".to_string()));
+        assert!(path("items").is_err());
+        assert!(path(".items[0").is_err());
+        assert!(path(".items..a").is_err());
+    }
+    #[test]
+    fn unit_structural_moves() {
+        let jsons: Vec<JV> = vec![
+            (&json!({"a": [1.0, {"b": null}, []], "c": true})).into(),
+            (&json!(2.0)).into(),
+        ];
+        let cursor = LeafCursor::new(jsons.into()).unwrap();
+        let path = |cursor: Option<LeafCursor>| cursor.map(|cursor| cursor.jq_path());
+        let a = cursor.first_child().unwrap();
+        assert_eq!(a.jq_path(), ".a");
+        assert_eq!(path(a.next_sibling()), Some(".c".to_string()));
+        assert_eq!(path(a.prev_sibling()), None);
+        assert_eq!(path(a.parent()), Some(".".to_string()));
+        assert_eq!(path(a.last_child()), Some(".a[2]".to_string()));
+        let b = a.first_child().unwrap().next_sibling().unwrap();
+        assert_eq!(b.jq_path(), ".a[1]");
+        assert_eq!(path(b.last_child()), Some(".a[1].b".to_string()));
+        assert_eq!(path(b.last_child().unwrap().first_child()), None);
+        // Empty containers have no children
+        assert_eq!(path(a.last_child().unwrap().first_child()), None);
+        // Top level values are siblings
+        let second = cursor.next_sibling().unwrap();
+        assert_eq!(second.top_index, 1);
+        assert_eq!(second.focus_position, FocusPosition::Value);
+        assert_eq!(path(second.next_sibling()), None);
+        assert_eq!(path(cursor.parent()), None);
+    }
+    #[test]
+    fn unit_status() {
+        let jsons: Vec<JV> = vec![
+            (&json!({"items": [1.0, {"a": "x"}]})).into(),
+            (&json!(null)).into(),
+        ];
+        let mut cursor = LeafCursor::new(jsons.into()).unwrap();
+        let mut statuses = vec![cursor.status()];
+        while let Some(()) = cursor.advance(&HashSet::new()) {
+            statuses.push(cursor.status());
+        }
+        statuses.dedup();
+        let expected = vec![
+            ".  object (1 key)  value 1/2",
+            ".items  array (2 elements)  value 1/2",
+            ".items[0]  number  value 1/2",
+            ".items[1]  object (1 key)  value 1/2",
+            ".items[1].a  string  value 1/2",
+            ".items[1]  object (1 key)  value 1/2",
+            ".items  array (2 elements)  value 1/2",
+            ".  object (1 key)  value 1/2",
+            ".  null  value 2/2",
+        ];
+        assert_eq!(statuses, expected);
+    }
     fn check_advancing_terminates(jsons: Vec<Value>) {
         let jsons: Vec<JV> = jsons.iter().map(|v| v.into()).collect();
         let folds = HashSet::new();
@@ -0,0 +1,194 @@
+use crate::jq::jv::JV;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    // Only present on this side
+    Added,
+    // Only present on the other side
+    Removed,
+    // Present on both sides, with different values
+    Changed,
+}
+
+// Marks are keyed by stripped paths, like folds. A mark on a container covers everything inside it.
+pub type DiffMarks = HashMap<(usize, Vec<usize>), DiffKind>;
+
+// Paths line up by array index and by object key, so the same value can have a different path on
+// each side.
+#[derive(Debug, Default)]
+pub struct Diff {
+    pub left: DiffMarks,
+    pub right: DiffMarks,
+    // Containers that are identical on both sides, so there's nothing to look at inside them
+    pub left_folds: HashSet<(usize, Vec<usize>)>,
+    pub right_folds: HashSet<(usize, Vec<usize>)>,
+}
+
+impl Diff {
+    pub fn new(left: &[JV], right: &[JV]) -> Self {
+        let mut diff = Diff::default();
+        for (i, (l, r)) in left.iter().zip(right.iter()).enumerate() {
+            diff.diff_values(l, r, (i, Vec::new()), (i, Vec::new()));
+        }
+        for i in right.len()..left.len() {
+            diff.left.insert((i, Vec::new()), DiffKind::Removed);
+        }
+        for i in left.len()..right.len() {
+            diff.right.insert((i, Vec::new()), DiffKind::Added);
+        }
+        diff
+    }
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty() && self.right.is_empty()
+    }
+    fn diff_values(
+        &mut self,
+        left: &JV,
+        right: &JV,
+        left_path: (usize, Vec<usize>),
+        right_path: (usize, Vec<usize>),
+    ) {
+        if left == right {
+            if let JV::Array(_) | JV::Object(_) = left {
+                self.left_folds.insert(left_path);
+                self.right_folds.insert(right_path);
+            }
+            return;
+        }
+        match (left, right) {
+            (JV::Array(l), JV::Array(r)) => {
+                let mut l_children = l.iter();
+                let mut r_children = r.iter();
+                let mut i = 0;
+                loop {
+                    match (l_children.next(), r_children.next()) {
+                        (Some(l_child), Some(r_child)) => self.diff_values(
+                            &l_child,
+                            &r_child,
+                            descend(&left_path, i),
+                            descend(&right_path, i),
+                        ),
+                        (Some(_), None) => {
+                            self.left.insert(descend(&left_path, i), DiffKind::Removed);
+                        }
+                        (None, Some(_)) => {
+                            self.right.insert(descend(&right_path, i), DiffKind::Added);
+                        }
+                        (None, None) => break,
+                    }
+                    i += 1;
+                }
+            }
+            (JV::Object(l), JV::Object(r)) => {
+                let r_indices: HashMap<&str, usize> =
+                    r.iter().enumerate().map(|(i, (k, _))| (k, i)).collect();
+                for (l_index, (key, l_child)) in l.iter().enumerate() {
+                    match r_indices.get(key) {
+                        Some(&r_index) => {
+                            let r_child = r.get(key).expect("Key should be in right object");
+                            self.diff_values(
+                                &l_child,
+                                &r_child,
+                                descend(&left_path, l_index),
+                                descend(&right_path, r_index),
+                            );
+                        }
+                        None => {
+                            self.left
+                                .insert(descend(&left_path, l_index), DiffKind::Removed);
+                        }
+                    }
+                }
+                for (r_index, (key, _)) in r.iter().enumerate() {
+                    if l.get(key).is_none() {
+                        self.right
+                            .insert(descend(&right_path, r_index), DiffKind::Added);
+                    }
+                }
+            }
+            _ => {
+                self.left.insert(left_path, DiffKind::Changed);
+                self.right.insert(right_path, DiffKind::Changed);
+            }
+        }
+    }
+}
+
+fn descend((top_index, path): &(usize, Vec<usize>), i: usize) -> (usize, Vec<usize>) {
+    let mut path = path.clone();
+    path.push(i);
+    (*top_index, path)
+}
+
+// The mark that applies to the value at the path, including any mark on one of its ancestors.
+pub fn mark_at(marks: &DiffMarks, (top_index, path): &(usize, Vec<usize>)) -> Option<DiffKind> {
+    (0..=path.len())
+        .rev()
+        .find_map(|len| marks.get(&(*top_index, path[..len].to_vec())).copied())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mark_at, Diff, DiffKind};
+    use crate::jq::jv::JV;
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+    use std::collections::HashSet;
+    fn to_jvs(values: Vec<Value>) -> Vec<JV> {
+        values.iter().map(JV::from).collect()
+    }
+    #[test]
+    fn unit_diff_identical() {
+        let values = to_jvs(vec![json!({"a": [1.0, 2.0]}), json!(3.0)]);
+        let diff = Diff::new(&values, &values);
+        assert!(diff.is_empty());
+        let expected: HashSet<_> = vec![(0, vec![])].into_iter().collect();
+        assert_eq!(diff.left_folds, expected);
+        assert_eq!(diff.right_folds, expected);
+    }
+    #[test]
+    fn unit_diff_arrays() {
+        let left = to_jvs(vec![json!([1.0, [2.0], 3.0, 4.0])]);
+        let right = to_jvs(vec![json!([1.0, [2.0], 5.0]), json!(null)]);
+        let diff = Diff::new(&left, &right);
+        assert_eq!(diff.left.get(&(0, vec![2])), Some(&DiffKind::Changed));
+        assert_eq!(diff.left.get(&(0, vec![3])), Some(&DiffKind::Removed));
+        assert_eq!(diff.right.get(&(0, vec![2])), Some(&DiffKind::Changed));
+        assert_eq!(diff.right.get(&(1, vec![])), Some(&DiffKind::Added));
+        assert_eq!(diff.left.len(), 2);
+        assert_eq!(diff.right.len(), 2);
+        let expected: HashSet<_> = vec![(0, vec![1])].into_iter().collect();
+        assert_eq!(diff.left_folds, expected);
+    }
+    #[test]
+    fn unit_diff_objects() {
+        let left = to_jvs(vec![
+            json!({"same": {"x": 1.0}, "gone": 1.0, "changed": "a"}),
+        ]);
+        let right = to_jvs(vec![
+            json!({"changed": ["b"], "new": 2.0, "same": {"x": 1.0}}),
+        ]);
+        let diff = Diff::new(&left, &right);
+        let index_of = |value: &JV, key: &str| match value {
+            JV::Object(obj) => obj.iter().position(|(k, _)| k == key).unwrap(),
+            _ => panic!("Expected an object"),
+        };
+        let left_mark = |key| diff.left.get(&(0, vec![index_of(&left[0], key)])).copied();
+        let right_mark = |key| {
+            diff.right
+                .get(&(0, vec![index_of(&right[0], key)]))
+                .copied()
+        };
+        assert_eq!(left_mark("gone"), Some(DiffKind::Removed));
+        assert_eq!(left_mark("changed"), Some(DiffKind::Changed));
+        assert_eq!(left_mark("same"), None);
+        assert_eq!(right_mark("new"), Some(DiffKind::Added));
+        assert_eq!(right_mark("changed"), Some(DiffKind::Changed));
+        let changed = (0, vec![index_of(&right[0], "changed"), 0]);
+        assert_eq!(mark_at(&diff.right, &changed), Some(DiffKind::Changed));
+        assert!(diff
+            .right_folds
+            .contains(&(0, vec![index_of(&right[0], "same")])));
+    }
+}
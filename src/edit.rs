@@ -0,0 +1,435 @@
+use crate::{
+    cursor::{CursorFrame, LeafCursor},
+    jq::jv::{JVObject, JV},
+};
+use std::{collections::HashSet, rc::Rc};
+
+// A change to the json, relative to the value under a cursor.
+#[derive(Debug, Clone)]
+pub enum Edit {
+    Replace(JV),
+    // Removes the value from its parent
+    Delete,
+    // Adds a new value right after this one. The key is needed if (and only if) the parent is an
+    // object.
+    InsertAfter(Option<String>, JV),
+    // Changes the key this value is stored under in its parent object
+    Rename(String),
+}
+
+#[derive(Debug)]
+pub struct Edited {
+    pub values: Rc<[JV]>,
+    pub folds: HashSet<(usize, Vec<usize>)>,
+    // Where the cursor should go: the changed value, or the nearest thing to it if it was deleted
+    pub cursor: (usize, Vec<usize>),
+}
+
+pub fn apply_edit(
+    cursor: &LeafCursor,
+    folds: &HashSet<(usize, Vec<usize>)>,
+    edit: Edit,
+) -> Result<Edited, String> {
+    let top_index = cursor.top_index;
+    let frames = &cursor.frames;
+    let path: Vec<usize> = frames.iter().map(CursorFrame::index).collect();
+    let parent = match frames.last() {
+        None => return apply_top_level_edit(cursor, folds, edit),
+        Some(parent) => parent,
+    };
+    let parent_path = &path[..path.len() - 1];
+    // The value that needs to be swapped in, and how deep it sits
+    let (value, depth, cursor_index, siblings) = match (edit, parent) {
+        (Edit::Replace(value), _) => (value, frames.len(), None, Siblings::Changed),
+        (Edit::Delete, CursorFrame::Array { index, json }) => {
+            let mut json = json.clone();
+            json.remove(*index as i32);
+            let cursor_index = clamp_index(*index, json.len() as usize);
+            (
+                json.into(),
+                frames.len() - 1,
+                cursor_index,
+                Siblings::Changed,
+            )
+        }
+        (
+            Edit::Delete,
+            CursorFrame::Object {
+                index, key, json, ..
+            },
+        ) => {
+            let mut json = json.clone();
+            json.delete(key.value());
+            let cursor_index = clamp_index(*index, json.len() as usize);
+            (
+                json.into(),
+                frames.len() - 1,
+                cursor_index,
+                Siblings::Changed,
+            )
+        }
+        (Edit::InsertAfter(None, value), CursorFrame::Array { index, json }) => {
+            let mut json = json.clone();
+            json.insert(*index as i32 + 1, value);
+            let siblings = Siblings::Inserted(*index + 1);
+            (json.into(), frames.len() - 1, Some(index + 1), siblings)
+        }
+        (Edit::InsertAfter(Some(key), value), CursorFrame::Object { index, json, .. }) => {
+            if json.get(&key).is_some() {
+                return Err(format!("Key {:?} already exists", key));
+            }
+            // Objects keep their keys in the order they were set, so the new key only ends up
+            // after the cursor's if we build the object again
+            let mut inserted = JVObject::new();
+            for (i, (k, v)) in json.iter().enumerate() {
+                inserted.set(k, v);
+                if i == *index {
+                    inserted.set(&key, value.clone());
+                }
+            }
+            let siblings = Siblings::Inserted(*index + 1);
+            (inserted.into(), frames.len() - 1, Some(index + 1), siblings)
+        }
+        (Edit::InsertAfter(Some(_), _), CursorFrame::Array { .. }) => {
+            return Err("Array elements don't have keys".to_string());
+        }
+        (Edit::InsertAfter(None, _), CursorFrame::Object { .. }) => {
+            return Err("A key is needed to insert into an object".to_string());
+        }
+        (
+            Edit::Rename(new_key),
+            CursorFrame::Object {
+                index, key, json, ..
+            },
+        ) => {
+            if new_key == key.value() {
+                return Ok(Edited {
+                    values: cursor.jsons.clone(),
+                    folds: folds.clone(),
+                    cursor: (top_index, path),
+                });
+            }
+            if json.get(&new_key).is_some() {
+                return Err(format!("Key {:?} already exists", new_key));
+            }
+            // Rebuilt so that the key stays where it was
+            let mut renamed = JVObject::new();
+            for (k, v) in json.iter() {
+                if k == key.value() {
+                    renamed.set(&new_key, v);
+                } else {
+                    renamed.set(k, v);
+                }
+            }
+            (
+                renamed.into(),
+                frames.len() - 1,
+                Some(*index),
+                Siblings::Unmoved,
+            )
+        }
+        (Edit::Rename(_), CursorFrame::Array { .. }) => {
+            return Err("Only values in objects have keys".to_string());
+        }
+    };
+    // A fold on the changed value itself still makes sense if there's something to fold
+    let foldable = is_foldable(&value);
+    let mut values = cursor.jsons.to_vec();
+    values[top_index] = rebuild(&frames[..depth], value);
+    // Folds inside the changed value might now point somewhere else, unless we know where its
+    // children went
+    let changed = &path[..depth];
+    let folds = folds
+        .iter()
+        .filter_map(|(fold_top, fold_path)| {
+            if *fold_top != top_index || !fold_path.starts_with(changed) {
+                return Some((*fold_top, fold_path.clone()));
+            }
+            if fold_path.len() == depth {
+                return if foldable {
+                    Some((top_index, fold_path.clone()))
+                } else {
+                    None
+                };
+            }
+            let mut fold_path = fold_path.clone();
+            let child = fold_path.get_mut(depth)?;
+            *child = siblings.new_index(*child)?;
+            Some((top_index, fold_path))
+        })
+        .collect();
+    let cursor = match cursor_index {
+        Some(i) => {
+            let mut cursor_path = parent_path.to_vec();
+            cursor_path.push(i);
+            cursor_path
+        }
+        None if depth == frames.len() => path,
+        // The container is now empty
+        None => parent_path.to_vec(),
+    };
+    Ok(Edited {
+        values: values.into(),
+        folds,
+        cursor: (top_index, cursor),
+    })
+}
+
+// Edits of the top level values, which live in a list rather than a json array
+fn apply_top_level_edit(
+    cursor: &LeafCursor,
+    folds: &HashSet<(usize, Vec<usize>)>,
+    edit: Edit,
+) -> Result<Edited, String> {
+    let top_index = cursor.top_index;
+    let mut values = cursor.jsons.to_vec();
+    let (folds, cursor_index) = match edit {
+        Edit::Replace(value) => {
+            let foldable = is_foldable(&value);
+            values[top_index] = value;
+            let folds = folds
+                .iter()
+                .filter(|(fold_top, fold_path)| {
+                    *fold_top != top_index || (foldable && fold_path.is_empty())
+                })
+                .cloned()
+                .collect();
+            (folds, top_index)
+        }
+        Edit::Delete => {
+            if values.len() == 1 {
+                return Err("Can't delete the only value".to_string());
+            }
+            values.remove(top_index);
+            let folds = folds
+                .iter()
+                .filter(|(fold_top, _)| *fold_top != top_index)
+                .map(|(fold_top, fold_path)| {
+                    let fold_top = if *fold_top > top_index {
+                        fold_top - 1
+                    } else {
+                        *fold_top
+                    };
+                    (fold_top, fold_path.clone())
+                })
+                .collect();
+            let cursor_index = clamp_index(top_index, values.len()).expect("values can't be empty");
+            (folds, cursor_index)
+        }
+        Edit::InsertAfter(None, value) => {
+            values.insert(top_index + 1, value);
+            let folds = folds
+                .iter()
+                .map(|(fold_top, fold_path)| {
+                    let fold_top = if *fold_top > top_index {
+                        fold_top + 1
+                    } else {
+                        *fold_top
+                    };
+                    (fold_top, fold_path.clone())
+                })
+                .collect();
+            (folds, top_index + 1)
+        }
+        Edit::InsertAfter(Some(_), _) | Edit::Rename(_) => {
+            return Err("Top level values don't have keys".to_string());
+        }
+    };
+    Ok(Edited {
+        values: values.into(),
+        folds,
+        cursor: (cursor_index, Vec::new()),
+    })
+}
+
+fn is_foldable(value: &JV) -> bool {
+    matches!(value, JV::Array(_) | JV::Object(_))
+}
+
+// What happened to the children of the value that was swapped in, so folds inside them can follow
+#[derive(Debug, Clone, Copy)]
+enum Siblings {
+    // They can't be matched up with the old ones
+    Changed,
+    // A new one was inserted at this index
+    Inserted(usize),
+    Unmoved,
+}
+
+impl Siblings {
+    fn new_index(self, index: usize) -> Option<usize> {
+        match self {
+            Siblings::Changed => None,
+            Siblings::Inserted(inserted) if index >= inserted => Some(index + 1),
+            Siblings::Inserted(_) | Siblings::Unmoved => Some(index),
+        }
+    }
+}
+
+// Puts the value back into its ancestors (which are immutable, so we rebuild them).
+fn rebuild(frames: &[CursorFrame], mut value: JV) -> JV {
+    for frame in frames.iter().rev() {
+        value = match frame {
+            CursorFrame::Array { index, json } => {
+                let mut json = json.clone();
+                json.set(*index as i32, value);
+                json.into()
+            }
+            CursorFrame::Object { key, json, .. } => {
+                let mut json = json.clone();
+                json.set(key.value(), value);
+                json.into()
+            }
+        };
+    }
+    value
+}
+
+// After a deletion, the cursor moves to the next sibling, or the previous one if it was the last.
+fn clamp_index(index: usize, len: usize) -> Option<usize> {
+    if len == 0 {
+        None
+    } else {
+        Some(std::cmp::min(index, len - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_edit, Edit, Edited};
+    use crate::{cursor::LeafCursor, jq::jv::JV};
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, value::Value};
+    use std::{collections::HashSet, rc::Rc};
+    fn cursor_at(values: Vec<Value>, path: (usize, Vec<usize>)) -> LeafCursor {
+        let jsons: Rc<[JV]> = values.iter().map(JV::from).collect::<Vec<_>>().into();
+        LeafCursor::try_from_stripped_path(jsons, path).unwrap()
+    }
+    fn values_of(edited: &Edited) -> Vec<Value> {
+        edited.values.iter().map(Value::from).collect()
+    }
+    #[test]
+    fn unit_edit_replace() {
+        let cursor = cursor_at(vec![json!({"a": [1.0, 2.0]})], (0, vec![0, 1]));
+        let folds = vec![(0, vec![]), (0, vec![0])].into_iter().collect();
+        let edited = apply_edit(&cursor, &folds, Edit::Replace((&json!({"b": null})).into()));
+        let edited = edited.unwrap();
+        assert_eq!(values_of(&edited), vec![json!({"a": [1.0, {"b": null}]})]);
+        assert_eq!(edited.folds, folds);
+        assert_eq!(edited.cursor, (0, vec![0, 1]));
+    }
+    #[test]
+    fn unit_edit_replace_folded() {
+        let cursor = cursor_at(vec![json!({"a": [[1.0], 2.0]})], (0, vec![0]));
+        let folds = vec![(0, vec![0]), (0, vec![0, 0])].into_iter().collect();
+        let edited = apply_edit(&cursor, &folds, Edit::Replace((&json!([3.0])).into()));
+        let expected: HashSet<_> = vec![(0, vec![0])].into_iter().collect();
+        assert_eq!(edited.unwrap().folds, expected);
+        let edited = apply_edit(&cursor, &folds, Edit::Replace((&json!(3.0)).into()));
+        assert_eq!(edited.unwrap().folds, HashSet::new());
+        let cursor = cursor_at(vec![json!([1.0])], (0, vec![]));
+        let folds = vec![(0, vec![])].into_iter().collect();
+        let edited = apply_edit(&cursor, &folds, Edit::Replace((&json!({})).into()));
+        assert_eq!(edited.unwrap().folds, folds);
+    }
+    #[test]
+    fn unit_edit_delete() {
+        let cursor = cursor_at(vec![json!([1.0, [2.0], 3.0])], (0, vec![2]));
+        let folds = vec![(0, vec![1])].into_iter().collect();
+        let edited = apply_edit(&cursor, &folds, Edit::Delete).unwrap();
+        assert_eq!(values_of(&edited), vec![json!([1.0, [2.0]])]);
+        assert_eq!(edited.folds, HashSet::new());
+        assert_eq!(edited.cursor, (0, vec![1]));
+        let cursor = cursor_at(vec![json!({"a": {"b": 1.0}})], (0, vec![0, 0]));
+        let edited = apply_edit(&cursor, &HashSet::new(), Edit::Delete).unwrap();
+        assert_eq!(values_of(&edited), vec![json!({"a": {}})]);
+        assert_eq!(edited.cursor, (0, vec![0]));
+    }
+    #[test]
+    fn unit_edit_top_level() {
+        let cursor = cursor_at(vec![json!(1.0), json!([2.0]), json!([3.0])], (1, vec![]));
+        let folds = vec![(1, vec![]), (2, vec![])].into_iter().collect();
+        let edited = apply_edit(&cursor, &folds, Edit::Delete).unwrap();
+        assert_eq!(values_of(&edited), vec![json!(1.0), json!([3.0])]);
+        let expected: HashSet<_> = vec![(1, vec![])].into_iter().collect();
+        assert_eq!(edited.folds, expected);
+        let edited = apply_edit(
+            &cursor,
+            &folds,
+            Edit::InsertAfter(None, (&json!(5.0)).into()),
+        );
+        let edited = edited.unwrap();
+        assert_eq!(
+            values_of(&edited),
+            vec![json!(1.0), json!([2.0]), json!(5.0), json!([3.0])]
+        );
+        let expected: HashSet<_> = vec![(1, vec![]), (3, vec![])].into_iter().collect();
+        assert_eq!(edited.folds, expected);
+        assert_eq!(edited.cursor, (2, vec![]));
+        let cursor = cursor_at(vec![json!(1.0)], (0, vec![]));
+        assert!(apply_edit(&cursor, &HashSet::new(), Edit::Delete).is_err());
+    }
+    #[test]
+    fn unit_edit_keys() {
+        let cursor = cursor_at(vec![json!({"a": {"b": 1.0}})], (0, vec![0]));
+        let edited = apply_edit(&cursor, &HashSet::new(), Edit::Rename("c".to_string())).unwrap();
+        assert_eq!(values_of(&edited), vec![json!({"c": {"b": 1.0}})]);
+        assert_eq!(edited.cursor, (0, vec![0]));
+        let cursor = cursor_at(vec![json!({"a": 1.0, "b": 2.0})], (0, vec![0]));
+        let key = cursor.current_key().unwrap();
+        let other_key = if key.value() == "a" { "b" } else { "a" };
+        let renamed = apply_edit(
+            &cursor,
+            &HashSet::new(),
+            Edit::Rename(other_key.to_string()),
+        );
+        assert!(renamed.is_err());
+        let value: JV = (&json!(true)).into();
+        let inserted = apply_edit(
+            &cursor,
+            &HashSet::new(),
+            Edit::InsertAfter(Some("d".to_string()), value.clone()),
+        );
+        let inserted = inserted.unwrap();
+        assert_eq!(
+            values_of(&inserted),
+            vec![json!({"a": 1.0, "b": 2.0, "d": true})]
+        );
+        let new_cursor =
+            LeafCursor::try_from_stripped_path(inserted.values, inserted.cursor).unwrap();
+        assert_eq!(new_cursor.current_key().unwrap().value(), "d");
+        let inserted = apply_edit(&cursor, &HashSet::new(), Edit::InsertAfter(None, value));
+        assert!(inserted.is_err());
+    }
+    #[test]
+    fn unit_edit_keys_in_place() {
+        let keys = |edited: &Edited| match &edited.values[0] {
+            JV::Object(obj) => obj.iter().map(|(k, _)| k.to_owned()).collect::<Vec<_>>(),
+            _ => panic!("Expected an object"),
+        };
+        let json: JV = (&json!({"a": [1.0], "b": [2.0], "c": [3.0]})).into();
+        let jsons: Rc<[JV]> = vec![json].into();
+        let cursor = LeafCursor::try_from_stripped_path(jsons, (0, vec![0])).unwrap();
+        let order: Vec<String> = match &cursor.jsons[0] {
+            JV::Object(obj) => obj.iter().map(|(k, _)| k.to_owned()).collect(),
+            _ => panic!("Expected an object"),
+        };
+        // Fold the values after the cursor's
+        let folds: HashSet<_> = vec![(0, vec![1]), (0, vec![2])].into_iter().collect();
+        let value: JV = (&json!(true)).into();
+        let edit = Edit::InsertAfter(Some("d".to_string()), value);
+        let inserted = apply_edit(&cursor, &folds, edit).unwrap();
+        let mut expected = order.clone();
+        expected.insert(1, "d".to_string());
+        assert_eq!(keys(&inserted), expected);
+        assert_eq!(inserted.cursor, (0, vec![1]));
+        let expected: HashSet<_> = vec![(0, vec![2]), (0, vec![3])].into_iter().collect();
+        assert_eq!(inserted.folds, expected);
+        let renamed = apply_edit(&cursor, &folds, Edit::Rename("z".to_string())).unwrap();
+        let mut expected = order;
+        expected[0] = "z".to_string();
+        assert_eq!(keys(&renamed), expected);
+        assert_eq!(renamed.cursor, (0, vec![0]));
+        assert_eq!(renamed.folds, folds);
+    }
+}
@@ -0,0 +1,88 @@
+// Turns a chain of queries from the view tree into something that can be run without jex
+
+use crate::format::{is_compressed_path, Format};
+
+// One jq program that does what the whole chain does. jex runs each query on every result of the
+// one before, which is exactly what jq's pipe does.
+pub fn jq_program(queries: &[&str]) -> String {
+    let queries: Vec<&str> = queries
+        .iter()
+        .map(|query| query.trim())
+        .filter(|&query| query != ".")
+        .collect();
+    match queries.as_slice() {
+        [] => ".".to_string(),
+        [query] => query.to_string(),
+        queries => queries
+            .iter()
+            .map(|query| format!("({})", query))
+            .collect::<Vec<_>>()
+            .join(" | "),
+    }
+}
+
+// A script that pipes the input through one jq per query. input is the file to read, or None for
+// stdin, and format is what it was read as.
+pub fn shell_script(queries: &[&str], input: Option<&str>, format: Format) -> String {
+    let mut script = "#!/bin/sh\n# Exported from jex\n".to_string();
+    if !format.jq_can_read() {
+        script
+            .push_str("# jq can't read the input's format, so it needs converting to json first\n");
+    }
+    if matches!(input, Some(input) if is_compressed_path(input)) {
+        script.push_str(
+            "# The input is compressed, so it needs decompressing before jq can read it\n",
+        );
+    }
+    let mut commands: Vec<String> = queries
+        .iter()
+        .map(|query| format!("jq {}", shell_quote(query.trim())))
+        .collect();
+    if let (Some(input), Some(first)) = (input, commands.first_mut()) {
+        first.push(' ');
+        first.push_str(&shell_quote(input));
+    }
+    script.push_str(&commands.join(" | "));
+    script.push('\n');
+    script
+}
+
+// Single quotes keep everything literal, except single quotes themselves
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{jq_program, shell_script};
+    use crate::format::Format;
+    use pretty_assertions::assert_eq;
+    #[test]
+    fn unit_jq_program() {
+        assert_eq!(jq_program(&["."]), ".");
+        assert_eq!(jq_program(&[".", " .items[] "]), ".items[]");
+        assert_eq!(
+            jq_program(&[".items[]", ".a, .b", "select(. > 1)"]),
+            "(.items[]) | (.a, .b) | (select(. > 1))"
+        );
+    }
+    #[test]
+    fn unit_shell_script() {
+        assert_eq!(
+            shell_script(
+                &[".items[]", ".name | test(\"x'y\")"],
+                Some("my data.json"),
+                Format::Json
+            ),
+            "#!/bin/sh\n# Exported from jex\njq '.items[]' 'my data.json' | jq '.name | test(\"x'\\''y\")'\n"
+        );
+        assert_eq!(
+            shell_script(&["."], None, Format::Yaml),
+            "#!/bin/sh\n# Exported from jex\n# jq can't read the input's format, so it needs converting to json first\njq '.'\n"
+        );
+        assert_eq!(
+            shell_script(&["."], Some("a.json5.gz"), Format::Json5),
+            "#!/bin/sh\n# Exported from jex\n# jq can't read the input's format, so it needs converting to json first\n# The input is compressed, so it needs decompressing before jq can read it\njq '.' 'a.json5.gz'\n"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{
+    cursor::{PathSegment, ValuePath},
+    jq::{
+        jv::JV,
+        query::{run_jq_query, JQ},
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+// Folds every array or object that matches, and keeps folding them when the view's values change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FoldRule {
+    // Values under this key, anywhere
+    Key(String),
+    // Values that this jq filter gives true for
+    Jq(String),
+}
+
+impl FoldRule {
+    // The folds the rule makes in the values from start on
+    pub fn folds(&self, jsons: &[JV], start: usize) -> Vec<(usize, Vec<usize>)> {
+        let mut folds = Vec::new();
+        match self {
+            FoldRule::Key(key) => {
+                for (top_index, json) in jsons.iter().enumerate().skip(start) {
+                    key_folds(key, json, &mut Vec::new(), &mut |path| {
+                        folds.push((top_index, path.to_vec()))
+                    });
+                }
+            }
+            FoldRule::Jq(filter) => {
+                let mut prog = match JQ::compile(&jq_program(filter)) {
+                    Ok(prog) => prog,
+                    Err(_) => return folds,
+                };
+                for (top_index, json) in jsons.iter().enumerate().skip(start) {
+                    let paths = run_jq_query(std::iter::once(json), &mut prog).unwrap_or_default();
+                    for path in paths {
+                        let path = jq_path_segments(&path).and_then(|segments| {
+                            ValuePath::from_segments(jsons, top_index, segments).ok()
+                        });
+                        folds.extend(path.map(ValuePath::strip_position));
+                    }
+                }
+            }
+        }
+        folds
+    }
+}
+
+// The paths of the containers the filter picks. Filters that fail on some values (like `length`
+// on booleans) just don't pick them.
+fn jq_program(filter: &str) -> String {
+    format!(
+        "path(.. | select((type == \"array\" or type == \"object\") and (try ({}) catch false)))",
+        filter
+    )
+}
+
+fn key_folds<F: FnMut(&[usize])>(key: &str, json: &JV, path: &mut Vec<usize>, f: &mut F) {
+    match json {
+        JV::Array(arr) => {
+            for (i, child) in arr.iter().enumerate() {
+                path.push(i);
+                key_folds(key, &child, path, f);
+                path.pop();
+            }
+        }
+        JV::Object(obj) => {
+            for (i, (k, child)) in obj.iter().enumerate() {
+                path.push(i);
+                if let (true, JV::Array(_)) | (true, JV::Object(_)) = (k == key, &child) {
+                    f(path);
+                }
+                key_folds(key, &child, path, f);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+// Turns a path from jq's path() (like `["items", 3]`) into segments
+fn jq_path_segments(path: &JV) -> Option<Vec<PathSegment>> {
+    match path {
+        JV::Array(arr) => arr
+            .iter()
+            .map(|segment| match segment {
+                JV::Number(i) => Some(PathSegment::Index(i.value() as i64)),
+                JV::String(key) => Some(PathSegment::Key(key.value().to_string())),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+// "key NAME" folds everything under NAME, and anything else is a jq filter
+impl FromStr for FoldRule {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(key) = s.strip_prefix("key ") {
+            return Ok(FoldRule::Key(key.trim().to_string()));
+        }
+        if s.is_empty() {
+            return Err("Fold rules can't be empty".to_string());
+        }
+        JQ::compile(&jq_program(s)).map_err(|errs| errs.join("\n"))?;
+        Ok(FoldRule::Jq(s.to_string()))
+    }
+}
+
+impl fmt::Display for FoldRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FoldRule::Key(key) => write!(f, "key {}", key),
+            FoldRule::Jq(filter) => write!(f, "{}", filter),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FoldRule;
+    use crate::jq::jv::JV;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    #[test]
+    fn unit_fold_rules() {
+        let jsons: Vec<JV> = vec![
+            (&json!({"metadata": {"a": 1.0}, "items": [{"metadata": [1.0]}, {"metadata": null}]}))
+                .into(),
+            (&json!({"metadata": {}})).into(),
+        ];
+        let folds = |rule: &str, start| {
+            let mut folds = rule.parse::<FoldRule>().unwrap().folds(&jsons, start);
+            folds.sort();
+            folds
+        };
+        assert_eq!(
+            folds("key metadata", 0),
+            vec![(0, vec![0, 0, 0]), (0, vec![1]), (1, vec![0])]
+        );
+        assert_eq!(folds("key metadata", 1), vec![(1, vec![0])]);
+        assert_eq!(folds("length > 1", 0), vec![(0, vec![]), (0, vec![0])]);
+        assert_eq!(folds("has(\"a\")", 0), vec![(0, vec![1])]);
+        assert!("".parse::<FoldRule>().is_err());
+        assert!("length >".parse::<FoldRule>().is_err());
+        assert_eq!(
+            "key  a b ".parse::<FoldRule>().unwrap().to_string(),
+            "key a b"
+        );
+    }
+}
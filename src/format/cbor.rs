@@ -0,0 +1,66 @@
+use super::{invalid_data, map_key, native_number, BytesEncoding, NativeNumber};
+use crate::jq::jv::JV;
+use serde_cbor::Value;
+use serde_json::{Number, Value as Json};
+use std::io;
+
+pub fn values<'a, R: io::Read + 'a>(
+    r: R,
+    bytes: BytesEncoding,
+) -> impl Iterator<Item = io::Result<JV>> + 'a {
+    serde_cbor::Deserializer::from_reader(r)
+        .into_iter::<Value>()
+        .map(move |value| Ok(JV::from(&to_json(value.map_err(invalid_data)?, bytes))))
+}
+
+pub fn write<W: io::Write>(mut w: W, values: &[JV]) -> io::Result<()> {
+    for value in values {
+        serde_cbor::to_writer(&mut w, &to_cbor(value)).map_err(invalid_data)?;
+    }
+    Ok(())
+}
+
+fn to_json(value: Value, bytes: BytesEncoding) -> Json {
+    match value {
+        Value::Null => Json::Null,
+        Value::Bool(b) => Json::Bool(b),
+        // CBOR integers go up to 64 bits in either direction, more than json numbers usually keep
+        Value::Integer(i) => Json::Number(
+            i.to_string()
+                .parse::<Number>()
+                .expect("Integers are valid numbers"),
+        ),
+        Value::Float(x) => x.into(),
+        Value::Bytes(b) => bytes.encode(&b).into(),
+        Value::Text(s) => s.into(),
+        Value::Array(arr) => arr.into_iter().map(|v| to_json(v, bytes)).collect(),
+        Value::Map(entries) => Json::Object(
+            entries
+                .into_iter()
+                .map(|(k, v)| (map_key(to_json(k, bytes)), to_json(v, bytes)))
+                .collect(),
+        ),
+        // Tags (like "this is a date") don't change what the value looks like
+        Value::Tag(_, value) => to_json(*value, bytes),
+        _ => Json::Null,
+    }
+}
+
+fn to_cbor(json: &JV) -> Value {
+    match json {
+        JV::Null(_) => Value::Null,
+        JV::Bool(b) => Value::Bool(b.value()),
+        JV::Number(x) => match native_number(x) {
+            NativeNumber::Signed(i) => Value::Integer(i.into()),
+            NativeNumber::Unsigned(u) => Value::Integer(u.into()),
+            NativeNumber::Float(x) => Value::Float(x),
+        },
+        JV::String(s) => Value::Text(s.value().to_owned()),
+        JV::Array(arr) => Value::Array(arr.iter().map(|v| to_cbor(&v)).collect()),
+        JV::Object(obj) => Value::Map(
+            obj.iter()
+                .map(|(k, v)| (Value::Text(k.to_owned()), to_cbor(&v)))
+                .collect(),
+        ),
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::{self, Read};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+
+// File extensions for compression, which hide the extension that says what the format is
+const EXTENSIONS: &[&str] = &["gz", "zst", "zstd", "bz2"];
+
+// Puts a streaming decompressor in front of compressed input, going by its magic bytes. Anything
+// else is passed through untouched.
+pub fn decompress<'a, R: Read + Send + 'a>(r: R) -> io::Result<Box<dyn Read + Send + 'a>> {
+    Ok(decompress_detecting(r)?.0)
+}
+
+// Like decompress, but also says whether the input was compressed
+pub fn decompress_detecting<'a, R: Read + Send + 'a>(
+    mut r: R,
+) -> io::Result<(Box<dyn Read + Send + 'a>, bool)> {
+    let mut magic = Vec::with_capacity(4);
+    (&mut r).take(4).read_to_end(&mut magic)?;
+    let r = io::Cursor::new(magic.clone()).chain(r);
+    let decompressed: Box<dyn Read + Send> = if magic.starts_with(GZIP_MAGIC) {
+        // Logs are often several gzip streams one after the other
+        Box::new(flate2::read::MultiGzDecoder::new(r))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        Box::new(zstd::Decoder::new(r)?)
+    } else if magic.starts_with(BZIP2_MAGIC) {
+        Box::new(bzip2::read::MultiBzDecoder::new(r))
+    } else {
+        return Ok((Box::new(r), false));
+    };
+    Ok((decompressed, true))
+}
+
+pub fn is_compressed_extension(extension: &str) -> bool {
+    EXTENSIONS.contains(&extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decompress, decompress_detecting};
+    use pretty_assertions::assert_eq;
+    use std::io::{Read, Write};
+    fn read_all(compressed: Vec<u8>) -> String {
+        let mut out = String::new();
+        decompress(compressed.as_slice())
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        out
+    }
+    #[test]
+    fn unit_decompress() {
+        let text = "{\"a\": 1}\n{\"b\": 2}\n";
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(text.as_bytes()).unwrap();
+        assert_eq!(read_all(gz.finish().unwrap()), text);
+        let zst = zstd::encode_all(text.as_bytes(), 0).unwrap();
+        assert_eq!(read_all(zst), text);
+        let mut bz = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bz.write_all(text.as_bytes()).unwrap();
+        assert_eq!(read_all(bz.finish().unwrap()), text);
+        assert_eq!(read_all(text.as_bytes().to_vec()), text);
+        assert_eq!(read_all(b"1".to_vec()), "1");
+    }
+    #[test]
+    fn unit_decompress_detecting() {
+        let zst = zstd::encode_all(&b"[1]"[..], 0).unwrap();
+        assert!(decompress_detecting(zst.as_slice()).unwrap().1);
+        assert!(!decompress_detecting(&b"[1]"[..]).unwrap().1);
+    }
+}
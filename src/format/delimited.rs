@@ -0,0 +1,96 @@
+use crate::jq::jv::{JVBool, JVNull, JVNumber, JVObject, JVString, JV};
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use std::{io, iter};
+
+// Each row becomes an object, keyed by the header row.
+pub fn values<'a, R: io::Read + 'a>(
+    r: R,
+    delimiter: u8,
+    infer_types: bool,
+) -> Box<dyn Iterator<Item = io::Result<JV>> + 'a> {
+    let mut reader = ReaderBuilder::new().delimiter(delimiter).from_reader(r);
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(err) => return Box::new(iter::once(Err(err.into()))),
+    };
+    let rows = reader
+        .into_records()
+        .map(move |record| Ok(to_object(&headers, &record?, infer_types)));
+    Box::new(rows)
+}
+
+fn to_object(headers: &StringRecord, record: &StringRecord, infer_types: bool) -> JV {
+    let mut obj = JVObject::new();
+    for (key, field) in headers.iter().zip(record.iter()) {
+        let value = if infer_types {
+            infer_type(field)
+        } else {
+            JVString::new(field).into()
+        };
+        obj.set(key, value);
+    }
+    obj.into()
+}
+
+// Only things that look exactly like json become numbers, so that (eg) zip codes with leading
+// zeros stay strings. Spaces around a number (as in "1, 2") are ignored.
+fn infer_type(field: &str) -> JV {
+    match field {
+        "" => JVNull::new().into(),
+        "true" => JVBool::new(true).into(),
+        "false" => JVBool::new(false).into(),
+        _ => {
+            let trimmed = field.trim();
+            serde_json::from_str::<serde_json::Number>(trimmed)
+                .ok()
+                .and_then(|_| JVNumber::from_literal(trimmed))
+                .map_or_else(|| JVString::new(field).into(), JV::from)
+        }
+    }
+}
+
+// The columns are every key that appears in any of the values, in the order they first appear.
+pub fn write<W: io::Write>(w: W, values: &[JV], delimiter: u8) -> io::Result<()> {
+    let mut columns: Vec<String> = Vec::new();
+    for (i, value) in values.iter().enumerate() {
+        let obj = match value {
+            JV::Object(obj) => obj,
+            _ => return Err(not_flat(i)),
+        };
+        for (key, _) in obj.iter() {
+            if !columns.iter().any(|column| column == key) {
+                columns.push(key.to_owned());
+            }
+        }
+    }
+    let mut writer = WriterBuilder::new().delimiter(delimiter).from_writer(w);
+    writer.write_record(&columns)?;
+    for (i, value) in values.iter().enumerate() {
+        let obj = match value {
+            JV::Object(obj) => obj,
+            _ => return Err(not_flat(i)),
+        };
+        let mut record = Vec::with_capacity(columns.len());
+        for column in columns.iter() {
+            let field = match obj.get(column) {
+                None | Some(JV::Null(_)) => String::new(),
+                Some(JV::Bool(b)) => b.value().to_string(),
+                Some(JV::Number(x)) => x.to_string(),
+                Some(JV::String(s)) => s.value().to_owned(),
+                Some(JV::Array(_)) | Some(JV::Object(_)) => return Err(not_flat(i)),
+            };
+            record.push(field);
+        }
+        writer.write_record(&record)?;
+    }
+    writer.flush()
+}
+
+fn not_flat(index: usize) -> io::Error {
+    let message = format!(
+        "Value {} isn't a flat object: only objects of strings, numbers, booleans and nulls can \
+         be saved as CSV",
+        index + 1
+    );
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,95 @@
+use std::{error::Error, fmt, io};
+
+// Where a value failed to parse. Lines and columns both start at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+// A line of newline-delimited json that didn't parse. Unlike other errors, the lines after it can
+// still be read.
+#[derive(Debug)]
+pub struct LineError {
+    pub line: usize,
+    pub err: serde_json::Error,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let location = Location {
+            line: self.err.line(),
+            column: self.err.column(),
+        };
+        let message = without_location(self.err.to_string(), Some(location));
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line,
+            self.err.column(),
+            message
+        )
+    }
+}
+
+impl Error for LineError {}
+
+pub fn is_bad_line(err: &io::Error) -> bool {
+    matches!(err.get_ref(), Some(err) if err.is::<LineError>())
+}
+
+// Splits an error into what went wrong and, if the parser knows, where
+pub fn describe(err: &io::Error) -> (String, Option<Location>) {
+    let location = err.get_ref().and_then(location);
+    (without_location(err.to_string(), location), location)
+}
+
+fn location(err: &(dyn Error + Send + Sync + 'static)) -> Option<Location> {
+    if let Some(err) = err.downcast_ref::<serde_json::Error>() {
+        // Errors that didn't come from the input (like io errors) are on line 0
+        if err.line() == 0 {
+            return None;
+        }
+        return Some(Location {
+            line: err.line(),
+            column: err.column(),
+        });
+    }
+    if let Some(err) = err.downcast_ref::<LineError>() {
+        return Some(Location {
+            line: err.line,
+            column: err.err.column(),
+        });
+    }
+    if let Some(err) = err.downcast_ref::<serde_yaml::Error>() {
+        let location = err.location()?;
+        return Some(Location {
+            line: location.line(),
+            column: location.column(),
+        });
+    }
+    if let Some(::json5::Error::Message {
+        location: Some(location),
+        ..
+    }) = err.downcast_ref::<::json5::Error>()
+    {
+        return Some(Location {
+            line: location.line,
+            column: location.column,
+        });
+    }
+    None
+}
+
+// Both serde_json and serde_yaml end their messages with the location
+fn without_location(message: String, location: Option<Location>) -> String {
+    let location = match location {
+        Some(location) => location,
+        None => return message,
+    };
+    let suffix = format!(" at line {} column {}", location.line, location.column);
+    match message.strip_suffix(&suffix) {
+        Some(message) => message.to_owned(),
+        None => message,
+    }
+}
@@ -0,0 +1,206 @@
+use crate::jq::jv::JV;
+use serde::Serialize;
+use serde_json::ser::PrettyFormatter;
+use std::{
+    fmt,
+    io::{self, BufRead},
+    str::FromStr,
+};
+
+// How many lines detect looks through for an indented one
+const DETECT_LINES: usize = 100;
+
+// How json is laid out when it's saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonLayout {
+    // Spaces per level, or None to put each value on a single line
+    pub indent: Option<usize>,
+    // Wrap the values in one array, rather than writing them one after another
+    pub array: bool,
+    pub sort_keys: bool,
+}
+
+impl Default for JsonLayout {
+    fn default() -> Self {
+        JsonLayout {
+            indent: Some(2),
+            array: false,
+            sort_keys: false,
+        }
+    }
+}
+
+impl JsonLayout {
+    // Guesses the layout a file was written with, so writing it back doesn't reformat it: the
+    // indent of its first indented line, or compact if none of its first lines are indented (like
+    // ndjson). We can only write spaces, so anything indented with tabs stays pretty.
+    pub fn detect<R: BufRead>(r: R) -> io::Result<Self> {
+        for line in r.lines().take(DETECT_LINES) {
+            let line = line?;
+            let indent = line.len() - line.trim_start_matches(&[' ', '\t'][..]).len();
+            if line[..indent].contains('\t') {
+                return Ok(JsonLayout::default());
+            }
+            if indent > 0 {
+                return Ok(JsonLayout {
+                    indent: Some(indent),
+                    ..JsonLayout::default()
+                });
+            }
+        }
+        Ok(JsonLayout {
+            indent: None,
+            ..JsonLayout::default()
+        })
+    }
+    pub fn write<W: io::Write>(self, mut w: W, values: &[JV]) -> io::Result<()> {
+        if self.array {
+            self.write_value(&mut w, values)?;
+            if self.indent.is_none() {
+                writeln!(w)?;
+            }
+            return Ok(());
+        }
+        for (i, v) in values.iter().enumerate() {
+            match self.indent {
+                // Compact values get a line each (making ndjson), and pretty ones a blank line
+                // between them
+                None => {
+                    self.write_value(&mut w, v)?;
+                    writeln!(w)?;
+                }
+                Some(_) => {
+                    if i != 0 {
+                        writeln!(w)?;
+                    }
+                    self.write_value(&mut w, v)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    fn write_value<W: io::Write, T: Serialize + ?Sized>(self, w: W, value: &T) -> io::Result<()> {
+        if self.sort_keys {
+            // serde_json's objects keep their keys sorted
+            let value = serde_json::to_value(value)?;
+            let layout = JsonLayout {
+                sort_keys: false,
+                ..self
+            };
+            return layout.write_value(w, &value);
+        }
+        match self.indent {
+            None => serde_json::to_writer(w, value)?,
+            Some(indent) => {
+                let indent = " ".repeat(indent);
+                let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+                let mut serializer = serde_json::Serializer::with_formatter(w, formatter);
+                value.serialize(&mut serializer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Space separated words: "pretty" (the default), "compact" or "ndjson" (the same thing),
+// "indent=N", "array" and "sorted"
+impl FromStr for JsonLayout {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut layout = JsonLayout::default();
+        for word in s.split_whitespace() {
+            match word {
+                "pretty" => layout.indent = layout.indent.or(Some(2)),
+                "compact" | "ndjson" => layout.indent = None,
+                "array" => layout.array = true,
+                "sorted" => layout.sort_keys = true,
+                _ => match word.strip_prefix("indent=") {
+                    Some(n) => {
+                        let n = n
+                            .parse()
+                            .map_err(|_| format!("Invalid indent width: {}", n))?;
+                        layout.indent = Some(n);
+                    }
+                    None => {
+                        return Err(format!(
+                            "Unknown layout {}: expected pretty, compact, ndjson, indent=N, array or sorted",
+                            word
+                        ))
+                    }
+                },
+            }
+        }
+        Ok(layout)
+    }
+}
+
+impl fmt::Display for JsonLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut words = vec![match self.indent {
+            None => "compact".to_string(),
+            Some(2) => "pretty".to_string(),
+            Some(n) => format!("indent={}", n),
+        }];
+        if self.array {
+            words.push("array".to_string());
+        }
+        if self.sort_keys {
+            words.push("sorted".to_string());
+        }
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonLayout;
+    use crate::jq::jv::JV;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    fn write(layout: &str, values: &[JV]) -> String {
+        let mut out = Vec::new();
+        let layout: JsonLayout = layout.parse().unwrap();
+        layout.write(&mut out, values).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+    #[test]
+    fn unit_json_layout_write() {
+        let values: Vec<JV> = vec![(&json!({"b": [1], "a": null})).into(), (&json!(2)).into()];
+        assert_eq!(
+            write("compact sorted", &values),
+            "{\"a\":null,\"b\":[1]}\n2\n"
+        );
+        assert_eq!(
+            write("ndjson array sorted", &values),
+            "[{\"a\":null,\"b\":[1]},2]\n"
+        );
+        assert_eq!(
+            write("indent=4 sorted", &values),
+            "{\n    \"a\": null,\n    \"b\": [\n        1\n    ]\n}\n2"
+        );
+        assert_eq!(write("pretty array sorted", &values[1..]), "[\n  2\n]");
+    }
+    #[test]
+    fn unit_json_layout_parse() {
+        for layout in ["pretty", "compact array", "indent=4 sorted"].iter() {
+            assert_eq!(layout.parse::<JsonLayout>().unwrap().to_string(), *layout);
+        }
+        assert_eq!("".parse(), Ok(JsonLayout::default()));
+        assert!("indent=x".parse::<JsonLayout>().is_err());
+        assert!("tiny".parse::<JsonLayout>().is_err());
+    }
+    #[test]
+    fn unit_json_layout_detect() {
+        let tests = vec![
+            ("{\"a\":1}\n{\"a\":2}\n", "compact"),
+            ("[1, 2]", "compact"),
+            ("{\n  \"a\": [\n    1\n  ]\n}\n", "pretty"),
+            ("[\n    1\n]", "indent=4"),
+            ("{\n\t\"a\": [\n\t\t1\n\t]\n}\n", "pretty"),
+        ];
+        for (text, expected) in tests {
+            let layout = JsonLayout::detect(text.as_bytes()).unwrap();
+            assert_eq!(layout.to_string(), expected, "{:?}", text);
+        }
+    }
+}
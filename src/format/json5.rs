@@ -0,0 +1,11 @@
+use super::invalid_data;
+use crate::jq::jv::JV;
+use std::io;
+
+// JSON5 is a superset of json and of jsonc (the json with comments that editors use for config
+// files), so this is how we read json leniently. Unlike json, a JSON5 file holds a single value.
+pub fn read<R: io::Read>(mut r: R) -> io::Result<JV> {
+    let mut s = String::new();
+    r.read_to_string(&mut s)?;
+    ::json5::from_str(&s).map_err(invalid_data)
+}
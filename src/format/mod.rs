@@ -0,0 +1,477 @@
+mod cbor;
+mod compression;
+mod delimited;
+mod error;
+mod json;
+mod json5;
+mod msgpack;
+mod yaml;
+
+pub use compression::{decompress, decompress_detecting};
+pub use error::{describe, is_bad_line, LineError, Location};
+pub use json::JsonLayout;
+
+use crate::jq::jv::{JVNumber, JV};
+use serde_json::{Deserializer, Value};
+use std::{
+    io::{self, BufRead},
+    iter,
+    path::Path,
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    // Json with comments, trailing commas, unquoted keys, NaN and friends
+    Json5,
+    // Newline-delimited json, read a line at a time so that bad lines can be skipped
+    JsonLines,
+    Yaml,
+    // CSV, TSV and friends. Rows are objects keyed by the header row, with every field a string
+    // unless infer_types is set.
+    Csv { delimiter: u8, infer_types: bool },
+    MessagePack { bytes: BytesEncoding },
+    Cbor { bytes: BytesEncoding },
+}
+
+// How byte strings (which MessagePack and CBOR have, but json doesn't) are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BytesEncoding {
+    Base64,
+    Hex,
+}
+
+impl Format {
+    // Guesses the format from a file's extension, looking past any compression extension (like
+    // the .gz in data.json.gz)
+    pub fn from_path(path: &str) -> Option<Self> {
+        let mut path = Path::new(path);
+        let mut extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        if compression::is_compressed_extension(&extension) {
+            path = Path::new(path.file_stem()?);
+            extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        }
+        match extension.as_str() {
+            "json" | "jsonl" | "ndjson" => Some(Format::Json),
+            "json5" | "jsonc" => Some(Format::Json5),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "csv" => Some(Format::csv(b',')),
+            "tsv" | "tab" => Some(Format::csv(b'\t')),
+            "msgpack" | "mpk" => Some(Format::MessagePack {
+                bytes: BytesEncoding::Base64,
+            }),
+            "cbor" => Some(Format::Cbor {
+                bytes: BytesEncoding::Base64,
+            }),
+            _ => None,
+        }
+    }
+    fn csv(delimiter: u8) -> Self {
+        Format::Csv {
+            delimiter,
+            infer_types: false,
+        }
+    }
+    // Turns fields that look like numbers, booleans or nothing at all into those, rather than
+    // strings. Only CSV needs this.
+    pub fn infer_types(self) -> Self {
+        match self {
+            Format::Csv { delimiter, .. } => Format::Csv {
+                delimiter,
+                infer_types: true,
+            },
+            format => format,
+        }
+    }
+    // Accepts json that's been written by hand, like config files with comments in them
+    pub fn lenient(self) -> Self {
+        match self {
+            Format::Json => Format::Json5,
+            format => format,
+        }
+    }
+    pub fn skip_bad_lines(self) -> Self {
+        match self {
+            Format::Json => Format::JsonLines,
+            format => format,
+        }
+    }
+    pub fn with_bytes(self, bytes: BytesEncoding) -> Self {
+        match self {
+            Format::MessagePack { .. } => Format::MessagePack { bytes },
+            Format::Cbor { .. } => Format::Cbor { bytes },
+            format => format,
+        }
+    }
+    // The top level values, parsed as they're needed (for formats that allow it)
+    pub fn values<'a, R: io::Read + 'a>(
+        self,
+        r: R,
+    ) -> Box<dyn Iterator<Item = io::Result<JV>> + 'a> {
+        match self {
+            Format::Json => Box::new(
+                Deserializer::from_reader(r)
+                    .into_iter::<JV>()
+                    .map(|value| Ok(value?)),
+            ),
+            Format::Json5 => Box::new(iter::once(json5::read(r))),
+            Format::JsonLines => Box::new(json_lines(io::BufReader::new(r))),
+            Format::Yaml => match yaml::read(r) {
+                Ok(values) => Box::new(values.into_iter().map(Ok)),
+                Err(err) => Box::new(iter::once(Err(err))),
+            },
+            Format::Csv {
+                delimiter,
+                infer_types,
+            } => delimited::values(r, delimiter, infer_types),
+            Format::MessagePack { bytes } => {
+                Box::new(msgpack::values(io::BufReader::new(r), bytes))
+            }
+            Format::Cbor { bytes } => Box::new(cbor::values(r, bytes)),
+        }
+    }
+    pub fn read<R: io::Read>(self, r: R) -> io::Result<Vec<JV>> {
+        self.values(r).collect()
+    }
+    pub fn is_json(self) -> bool {
+        matches!(self, Format::Json | Format::Json5 | Format::JsonLines)
+    }
+    // jq reads streams of json, but not the extra syntax JSON5 allows
+    pub fn jq_can_read(self) -> bool {
+        matches!(self, Format::Json | Format::JsonLines)
+    }
+    // What gets lost when values read in this format are written back over the file
+    pub fn write_back_loss(self) -> Option<&'static str> {
+        match self {
+            Format::Json5 => Some("Its comments and JSON5 syntax will be lost."),
+            Format::Yaml => Some("Its comments and anchors will be lost."),
+            Format::MessagePack { .. } | Format::Cbor { .. } => {
+                Some("Its byte strings will be written back as text.")
+            }
+            Format::Json | Format::JsonLines | Format::Csv { .. } => None,
+        }
+    }
+    pub fn write<W: io::Write>(self, w: W, values: &[JV]) -> io::Result<()> {
+        self.write_with_layout(w, values, JsonLayout::default())
+    }
+    // The layout only matters for json
+    pub fn write_with_layout<W: io::Write>(
+        self,
+        w: W,
+        values: &[JV],
+        layout: JsonLayout,
+    ) -> io::Result<()> {
+        match self {
+            // Plain json is valid JSON5 too
+            Format::Json | Format::Json5 | Format::JsonLines => layout.write(w, values),
+            Format::Yaml => yaml::write(w, values),
+            Format::Csv { delimiter, .. } => delimited::write(w, values, delimiter),
+            Format::MessagePack { .. } => msgpack::write(w, values),
+            Format::Cbor { .. } => cbor::write(w, values),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "json5" | "jsonc" => Ok(Format::Json5),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            "csv" => Ok(Format::csv(b',')),
+            "tsv" => Ok(Format::csv(b'\t')),
+            "msgpack" => Ok(Format::MessagePack {
+                bytes: BytesEncoding::Base64,
+            }),
+            "cbor" => Ok(Format::Cbor {
+                bytes: BytesEncoding::Base64,
+            }),
+            _ => Err(format!(
+                "Unknown format {}: expected json, json5, yaml, csv, tsv, msgpack or cbor",
+                s
+            )),
+        }
+    }
+}
+
+impl BytesEncoding {
+    fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            BytesEncoding::Base64 => base64::encode(bytes),
+            BytesEncoding::Hex => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+}
+
+impl FromStr for BytesEncoding {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base64" => Ok(BytesEncoding::Base64),
+            "hex" => Ok(BytesEncoding::Hex),
+            _ => Err(format!("Unknown encoding {}: expected base64 or hex", s)),
+        }
+    }
+}
+
+// How a number is written in formats that have integers and doubles, but not arbitrary literals
+enum NativeNumber {
+    Signed(i64),
+    Unsigned(u64),
+    Float(f64),
+}
+
+fn native_number(x: &JVNumber) -> NativeNumber {
+    if let Some(literal) = x.literal() {
+        if let Ok(i) = literal.parse::<i64>() {
+            return NativeNumber::Signed(i);
+        }
+        if let Ok(u) = literal.parse::<u64>() {
+            return NativeNumber::Unsigned(u);
+        }
+    }
+    let x = x.value();
+    if x.fract() == 0.0 && x.abs() < 9_007_199_254_740_992.0 {
+        NativeNumber::Signed(x as i64)
+    } else {
+        NativeNumber::Float(x)
+    }
+}
+
+// Json only has string keys, so other keys are written as json
+fn map_key(key: Value) -> String {
+    match key {
+        Value::String(s) => s,
+        key => key.to_string(),
+    }
+}
+
+// A line that doesn't parse is a LineError, and the lines after it are still read
+fn json_lines<R: BufRead>(mut r: R) -> impl Iterator<Item = io::Result<JV>> {
+    let mut line = 0;
+    let mut buf = Vec::new();
+    iter::from_fn(move || loop {
+        buf.clear();
+        line += 1;
+        match r.read_until(b'\n', &mut buf) {
+            Ok(0) => return None,
+            Ok(_) => {}
+            Err(err) => return Some(Err(err)),
+        }
+        if buf.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        // Without the newline, so errors at the end of the line are still on it
+        let text = buf.strip_suffix(b"\n").unwrap_or(&buf);
+        let value = serde_json::from_slice(text);
+        return Some(value.map_err(|err| invalid_data(LineError { line, err })));
+    })
+}
+
+// Compressed files can be read, but we only write plain ones
+pub fn is_compressed_path(path: &str) -> bool {
+    let extension = Path::new(path).extension().and_then(|e| e.to_str());
+    matches!(extension, Some(e) if compression::is_compressed_extension(&e.to_ascii_lowercase()))
+}
+
+fn invalid_data<E: std::error::Error + Send + Sync + 'static>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BytesEncoding, Format};
+    use crate::jq::jv::JV;
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+    fn read(format: Format, input: &str) -> Vec<Value> {
+        let values = format.read(input.as_bytes()).unwrap();
+        values.iter().map(Value::from).collect()
+    }
+    #[test]
+    fn unit_yaml_read() {
+        let input = "
+kind: Pod
+metadata:
+  name: web
+  labels: {app: web}
+spec:
+  ports:
+    - 80
+    - 443.5
+  200: ok
+  enabled: true
+  true: yes
+  big: 123456789012345678901
+---
+- null
+- second document
+";
+        // The big number has to come from a string to keep all of its digits
+        let expected: Value = serde_json::from_str(
+            r#"{
+                "kind": "Pod",
+                "metadata": {"name": "web", "labels": {"app": "web"}},
+                "spec": {
+                    "ports": [80.0, 443.5],
+                    "200": "ok",
+                    "enabled": true,
+                    "true": "yes",
+                    "big": 123456789012345678901
+                }
+            }"#,
+        )
+        .unwrap();
+        let expected = vec![expected, json!([null, "second document"])];
+        assert_eq!(read(Format::Yaml, input), expected);
+    }
+    #[test]
+    fn unit_yaml_roundtrip() {
+        let values = vec![
+            json!({"a": [1.0, 2.5, "three", null], "b": {"c": false}}),
+            json!("just a string"),
+        ];
+        let jvs: Vec<JV> = values.iter().map(JV::from).collect();
+        let mut out = Vec::new();
+        Format::Yaml.write(&mut out, &jvs).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(read(Format::Yaml, &out), values);
+    }
+    #[test]
+    fn unit_csv_read() {
+        let input = "name,zip,age,member,note\nann,02139,31,true,\nbob,\"9,5\",4.5e1,false,hi\n";
+        let expected = vec![
+            json!({"name": "ann", "zip": "02139", "age": "31", "member": "true", "note": ""}),
+            json!({"name": "bob", "zip": "9,5", "age": "4.5e1", "member": "false", "note": "hi"}),
+        ];
+        assert_eq!(read(Format::csv(b','), input), expected);
+        let expected = vec![
+            json!({"name": "ann", "zip": "02139", "age": 31.0, "member": true, "note": null}),
+            json!({"name": "bob", "zip": "9,5", "age": 45.0, "member": false, "note": "hi"}),
+        ];
+        assert_eq!(read(Format::csv(b',').infer_types(), input), expected);
+        let tsv = "a\tb\n1\tx y\n";
+        let expected = vec![json!({"a": 1.0, "b": "x y"})];
+        assert_eq!(read(Format::csv(b'\t').infer_types(), tsv), expected);
+        let padded = "a,b,c\n1, 2, x\n";
+        let expected = vec![json!({"a": 1.0, "b": 2.0, "c": " x"})];
+        assert_eq!(read(Format::csv(b',').infer_types(), padded), expected);
+    }
+    #[test]
+    fn unit_csv_write() {
+        let values = [json!({"a": 1.0, "b": "x,y"}), json!({"c": null, "a": true})];
+        let jvs: Vec<JV> = values.iter().map(JV::from).collect();
+        let mut out = Vec::new();
+        Format::csv(b',').write(&mut out, &jvs).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "a,b,c\n1,\"x,y\",\ntrue,,\n"
+        );
+        let nested: Vec<JV> = vec![(&json!({"a": [1.0]})).into()];
+        assert!(Format::csv(b',').write(Vec::new(), &nested).is_err());
+    }
+    #[test]
+    fn unit_binary_roundtrip() {
+        let values = vec![
+            json!({"a": [1.0, 2.5, "three", null], "b": {"c": false}}),
+            json!(-7.0),
+        ];
+        let jvs: Vec<JV> = values.iter().map(JV::from).collect();
+        for format in ["msgpack", "cbor"].iter() {
+            let format: Format = format.parse().unwrap();
+            let mut out = Vec::new();
+            format.write(&mut out, &jvs).unwrap();
+            let read: Vec<Value> = format
+                .read(&out[..])
+                .unwrap()
+                .iter()
+                .map(Value::from)
+                .collect();
+            assert_eq!(read, values);
+        }
+    }
+    #[test]
+    fn unit_binary_bytes() {
+        let msgpack = rmpv::Value::Map(vec![
+            (1.into(), rmpv::Value::Binary(vec![0xde, 0xad])),
+            ("ext".into(), rmpv::Value::Ext(-1, vec![0xff])),
+        ]);
+        let mut input = Vec::new();
+        rmpv::encode::write_value(&mut input, &msgpack).unwrap();
+        let format = Format::MessagePack {
+            bytes: BytesEncoding::Hex,
+        };
+        let values = format.read(&input[..]).unwrap();
+        assert_eq!(
+            Value::from(&values[0]),
+            json!({"1": "dead", "ext": {"type": -1.0, "data": "ff"}})
+        );
+        let cbor = serde_cbor::Value::Tag(24, Box::new(serde_cbor::Value::Bytes(b"jex".to_vec())));
+        let input = serde_cbor::to_vec(&cbor).unwrap();
+        let values = Format::Cbor {
+            bytes: BytesEncoding::Base64,
+        }
+        .read(&input[..])
+        .unwrap();
+        assert_eq!(Value::from(&values[0]), json!("amV4"));
+    }
+    #[test]
+    fn unit_json5_read() {
+        let input = r#"
+// tsconfig.json
+{
+    compilerOptions: {
+        "target": 'es2017', /* not es5 */
+        "lib": ["dom", "es2017",],
+    },
+    ratio: .5,
+    mask: 0xff,
+    missing: NaN,
+}
+"#;
+        let expected = json!({
+            "compilerOptions": {"target": "es2017", "lib": ["dom", "es2017"]},
+            "ratio": 0.5,
+            "mask": 255.0,
+            // NaN isn't json, so it comes out as null
+            "missing": null,
+        });
+        assert_eq!(read(Format::Json.lenient(), input), vec![expected]);
+        assert!(Format::Json.read(input.as_bytes()).is_err());
+        // It's still NaN while it's being viewed
+        let values = Format::Json5
+            .read("[NaN, Infinity, -Infinity]".as_bytes())
+            .unwrap();
+        match &values[0] {
+            JV::Array(arr) => {
+                let shown: Vec<String> = arr
+                    .iter()
+                    .map(|x| match x {
+                        JV::Number(x) => x.to_string(),
+                        _ => panic!("Expected a number"),
+                    })
+                    .collect();
+                assert_eq!(shown, vec!["NaN", "Infinity", "-Infinity"]);
+            }
+            _ => panic!("Expected an array"),
+        }
+    }
+    #[test]
+    fn unit_format_from_path() {
+        assert_eq!(Format::from_path("deploy.YML"), Some(Format::Yaml));
+        assert_eq!(Format::from_path("data.json"), Some(Format::Json));
+        assert_eq!(Format::from_path("settings.jsonc"), Some(Format::Json5));
+        assert_eq!(
+            Format::from_path("events.cbor"),
+            Some(Format::Cbor {
+                bytes: BytesEncoding::Base64
+            })
+        );
+        assert_eq!(Format::from_path("data.tsv"), Some(Format::csv(b'\t')));
+        assert_eq!(Format::from_path("events.yaml.zst"), Some(Format::Yaml));
+        assert_eq!(Format::from_path("events.gz"), None);
+        assert_eq!(Format::from_path("data"), None);
+    }
+}
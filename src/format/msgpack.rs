@@ -0,0 +1,74 @@
+use super::{invalid_data, map_key, native_number, BytesEncoding, NativeNumber};
+use crate::jq::jv::JV;
+use rmpv::Value;
+use serde_json::{json, Value as Json};
+use std::{
+    io::{self, BufRead},
+    iter,
+};
+
+// MessagePack has no framing: a stream is just one value after another
+pub fn values<R: BufRead>(mut r: R, bytes: BytesEncoding) -> impl Iterator<Item = io::Result<JV>> {
+    iter::from_fn(move || {
+        match r.fill_buf() {
+            Ok([]) => return None,
+            Ok(_) => {}
+            Err(err) => return Some(Err(err)),
+        }
+        let value = rmpv::decode::read_value(&mut r).map_err(invalid_data);
+        Some(value.map(|value| JV::from(&to_json(value, bytes))))
+    })
+}
+
+pub fn write<W: io::Write>(mut w: W, values: &[JV]) -> io::Result<()> {
+    for value in values {
+        rmpv::encode::write_value(&mut w, &to_msgpack(value)).map_err(invalid_data)?;
+    }
+    Ok(())
+}
+
+fn to_json(value: Value, bytes: BytesEncoding) -> Json {
+    match value {
+        Value::Nil => Json::Null,
+        Value::Boolean(b) => Json::Bool(b),
+        Value::Integer(i) => match i.as_i64() {
+            Some(i) => i.into(),
+            None => i.as_u64().expect("Integers are i64 or u64").into(),
+        },
+        Value::F32(x) => f64::from(x).into(),
+        Value::F64(x) => x.into(),
+        // Strings that aren't valid utf-8 are treated like binary
+        Value::String(s) => match s.as_str() {
+            Some(s) => s.into(),
+            None => bytes.encode(s.as_bytes()).into(),
+        },
+        Value::Binary(b) => bytes.encode(&b).into(),
+        Value::Array(arr) => arr.into_iter().map(|v| to_json(v, bytes)).collect(),
+        Value::Map(entries) => Json::Object(
+            entries
+                .into_iter()
+                .map(|(k, v)| (map_key(to_json(k, bytes)), to_json(v, bytes)))
+                .collect(),
+        ),
+        Value::Ext(kind, data) => json!({"type": kind, "data": bytes.encode(&data)}),
+    }
+}
+
+fn to_msgpack(json: &JV) -> Value {
+    match json {
+        JV::Null(_) => Value::Nil,
+        JV::Bool(b) => Value::Boolean(b.value()),
+        JV::Number(x) => match native_number(x) {
+            NativeNumber::Signed(i) => i.into(),
+            NativeNumber::Unsigned(u) => u.into(),
+            NativeNumber::Float(x) => x.into(),
+        },
+        JV::String(s) => s.value().into(),
+        JV::Array(arr) => Value::Array(arr.iter().map(|v| to_msgpack(&v)).collect()),
+        JV::Object(obj) => Value::Map(
+            obj.iter()
+                .map(|(k, v)| (k.into(), to_msgpack(&v)))
+                .collect(),
+        ),
+    }
+}
@@ -0,0 +1,46 @@
+use super::{invalid_data, native_number, NativeNumber};
+use crate::jq::jv::{JVNumber, JV};
+use serde::Deserialize;
+use serde_yaml::{Number, Value};
+use std::io;
+
+// Each document in the stream becomes a top level value.
+pub fn read<R: io::Read>(mut r: R) -> io::Result<Vec<JV>> {
+    let mut s = String::new();
+    r.read_to_string(&mut s)?;
+    serde_yaml::Deserializer::from_str(&s)
+        .map(|document| JV::deserialize(document).map_err(invalid_data))
+        .collect()
+}
+
+pub fn write<W: io::Write>(mut w: W, values: &[JV]) -> io::Result<()> {
+    for value in values {
+        serde_yaml::to_writer(&mut w, &to_yaml(value)).map_err(invalid_data)?;
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+fn to_yaml(json: &JV) -> Value {
+    match json {
+        JV::Null(_) => Value::Null,
+        JV::Bool(b) => Value::Bool(b.value()),
+        JV::Number(x) => Value::Number(number_to_yaml(x)),
+        JV::String(s) => Value::String(s.value().to_owned()),
+        JV::Array(arr) => Value::Sequence(arr.iter().map(|v| to_yaml(&v)).collect()),
+        JV::Object(obj) => Value::Mapping(
+            obj.iter()
+                .map(|(k, v)| (Value::String(k.to_owned()), to_yaml(&v)))
+                .collect(),
+        ),
+    }
+}
+
+// Yaml can't keep arbitrary number literals, so only integer ones survive
+fn number_to_yaml(x: &JVNumber) -> Number {
+    match native_number(x) {
+        NativeNumber::Signed(i) => i.into(),
+        NativeNumber::Unsigned(u) => u.into(),
+        NativeNumber::Float(x) => x.into(),
+    }
+}
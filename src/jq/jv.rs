@@ -1,28 +1,71 @@
-use super::jv_raw::{JVKind, JVRaw};
-pub use super::jv_raw::{ObjectIterator, ObjectValuesIterator, OwnedObjectIterator};
+use super::jv_raw::{self, JVKind, JVRaw};
 use serde::{
-    de::{MapAccess, SeqAccess, Visitor},
+    de::{self, MapAccess, SeqAccess, Visitor},
     ser::{SerializeMap, SerializeSeq},
     Deserialize, Serialize, Serializer,
 };
-use serde_json::value::Value;
+use serde_json::value::{Number, Value};
 use std::{
+    borrow::Borrow,
+    collections::HashMap,
     convert::{TryFrom, TryInto},
-    fmt,
+    fmt::{self, Write},
+    hash::Hash,
+    rc::Rc,
+    str,
 };
 
+// With serde_json's arbitrary_precision feature, numbers are deserialized as a map from this key to
+// the literal.
+const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct JVNull(JVRaw);
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct JVBool(JVRaw);
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct JVNumber(JVRaw);
+// jq stores every number as a double, so literals that a double can't represent exactly (like 64
+// bit ids) are kept next to the jq value instead: numbers hold their own literal, and arrays and
+// objects hold the literals of the numbers inside them. jq doesn't know about them, so values it
+// computes only have their doubles.
+#[derive(Debug, Clone)]
+pub struct JVNumber(JVRaw, Option<Rc<str>>);
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct JVString(JVRaw);
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct JVArray(JVRaw);
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct JVObject(JVRaw);
+#[derive(Debug, Clone)]
+pub struct JVArray(JVRaw, Option<Rc<ArrayLiterals>>);
+#[derive(Debug, Clone)]
+pub struct JVObject(JVRaw, Option<Rc<ObjectLiterals>>);
+
+// The literals somewhere inside an array or object, by index or key. Values without any are left
+// out, and so are tables that would be empty.
+type ArrayLiterals = HashMap<i32, Literals>;
+type ObjectLiterals = HashMap<String, Literals>;
+#[derive(Debug, Clone)]
+enum Literals {
+    Number(Rc<str>),
+    Array(Rc<ArrayLiterals>),
+    Object(Rc<ObjectLiterals>),
+}
+
+// Values are equal when jq thinks they are, whatever literals they were parsed from
+impl PartialEq for JVNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+impl Eq for JVNumber {}
+impl PartialEq for JVArray {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+impl Eq for JVArray {}
+impl PartialEq for JVObject {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+impl Eq for JVObject {}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum JV {
@@ -52,11 +95,171 @@ impl JVBool {
 }
 impl JVNumber {
     pub fn new(x: f64) -> Self {
-        JVNumber(JVRaw::number(x))
+        JVNumber(JVRaw::number(x), None)
+    }
+    // Parses a json number, remembering the literal if the double loses any of it.
+    pub fn from_literal(literal: &str) -> Option<Self> {
+        let x: f64 = literal.parse().ok()?;
+        if round_trips(literal, x) {
+            Some(JVNumber::new(x))
+        } else {
+            Some(JVNumber(JVRaw::number(x), Some(literal.into())))
+        }
     }
     pub fn value(&self) -> f64 {
         self.0.number_value()
     }
+    // The literal this number was parsed from, if the double doesn't represent it exactly.
+    pub fn literal(&self) -> Option<&str> {
+        self.1.as_deref()
+    }
+}
+// Shows the original literal if there is one, and integers without a trailing ".0". NaN and the
+// infinities are spelled the way JSON5 writes them.
+impl fmt::Display for JVNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(literal) = self.literal() {
+            return f.write_str(literal);
+        }
+        match self.value() {
+            x if x.is_nan() => f.write_str("NaN"),
+            x if x == f64::INFINITY => f.write_str("Infinity"),
+            x if x == f64::NEG_INFINITY => f.write_str("-Infinity"),
+            x => write!(f, "{}", x),
+        }
+    }
+}
+
+// Whether printing the double gives back the literal's number. Literals with at most 15
+// significant digits always do, as long as the double isn't subnormal, so the common case doesn't
+// have to print it.
+fn round_trips(literal: &str, x: f64) -> bool {
+    let literal = match Decimal::parse(literal) {
+        Some(literal) => literal,
+        None => return false,
+    };
+    if literal.count == 0 || (literal.count <= 15 && x.is_normal()) {
+        return true;
+    }
+    if !x.is_finite() {
+        return false;
+    }
+    // The shortest form of a double is at most 24 bytes, like "-2.2250738585072014e-308"
+    let mut buf = StackBuffer {
+        buf: [0; 32],
+        len: 0,
+    };
+    write!(buf, "{:e}", x).expect("Doubles should fit in the buffer");
+    let printed = str::from_utf8(&buf.buf[..buf.len]).expect("Doubles should print as ascii");
+    Decimal::parse(printed) == Some(literal)
+}
+
+struct StackBuffer {
+    buf: [u8; 32],
+    len: usize,
+}
+impl fmt::Write for StackBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        let dest = self.buf.get_mut(self.len..end).ok_or(fmt::Error)?;
+        dest.copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+// A decimal number as its sign, significant digits and the position of the decimal point before
+// them, so that different ways of writing the same number (eg, "1.50", "15e-1") compare equal.
+// The digits are left in the mantissa, to avoid copying them out.
+#[derive(Debug)]
+struct Decimal<'a> {
+    negative: bool,
+    mantissa: &'a str,
+    // Leading zeros to skip, and then how many digits are significant
+    zeros: usize,
+    count: usize,
+    point: i64,
+}
+impl<'a> Decimal<'a> {
+    fn parse(s: &'a str) -> Option<Self> {
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (mantissa, exponent) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], s[i + 1..].parse::<i64>().ok()?),
+            None => (s, 0),
+        };
+        let int_len = mantissa.find('.').unwrap_or(mantissa.len());
+        let frac_part = mantissa.get(int_len + 1..).unwrap_or("");
+        if !mantissa[..int_len]
+            .bytes()
+            .chain(frac_part.bytes())
+            .all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+        let zeros = Self::digits_of(mantissa).take_while(|&c| c == b'0').count();
+        let mut count = 0;
+        for (i, c) in Self::digits_of(mantissa).enumerate() {
+            if c != b'0' {
+                count = i + 1 - zeros;
+            }
+        }
+        Some(Decimal {
+            negative,
+            mantissa,
+            zeros,
+            count,
+            point: int_len as i64 - zeros as i64 + exponent,
+        })
+    }
+    fn digits_of(mantissa: &'a str) -> impl Iterator<Item = u8> + 'a {
+        mantissa.bytes().filter(|c| *c != b'.')
+    }
+    fn digits(&self) -> impl Iterator<Item = u8> + 'a {
+        Self::digits_of(self.mantissa)
+            .skip(self.zeros)
+            .take(self.count)
+    }
+}
+impl<'a> PartialEq for Decimal<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        // Zero is zero, whatever its sign
+        if self.count == 0 || other.count == 0 {
+            return self.count == other.count;
+        }
+        self.negative == other.negative
+            && self.point == other.point
+            && self.count == other.count
+            && self.digits().eq(other.digits())
+    }
+}
+
+// Writes the literal if there is one, and integers as integers.
+fn serialize_number<S: Serializer>(x: &JVNumber, serializer: S) -> Result<S::Ok, S::Error> {
+    if let Some(literal) = x.literal() {
+        if let Ok(i) = literal.parse::<i64>() {
+            return serializer.serialize_i64(i);
+        }
+        if let Ok(u) = literal.parse::<u64>() {
+            return serializer.serialize_u64(u);
+        }
+        if let Ok(n) = literal.parse::<Number>() {
+            return n.serialize(serializer);
+        }
+    }
+    let x = x.value();
+    // jq prints infinities as the biggest double there is, rather than null
+    if x.is_infinite() {
+        return serializer.serialize_f64(f64::MAX.copysign(x));
+    }
+    let is_integer = x.fract() == 0.0 && !(x == 0.0 && x.is_sign_negative());
+    if is_integer && x >= i64::MIN as f64 && x < i64::MAX as f64 {
+        serializer.serialize_i64(x as i64)
+    } else {
+        serializer.serialize_f64(x)
+    }
 }
 impl JVString {
     pub fn new(s: &str) -> Self {
@@ -66,11 +269,43 @@ impl JVString {
         self.0.string_value()
     }
 }
+// Records the literals of a value put in an array or object, or forgets the ones of the value it
+// replaced.
+fn set_literals<K, Q>(
+    table: &mut Option<Rc<HashMap<K, Literals>>>,
+    key: &Q,
+    literals: Option<Literals>,
+) where
+    K: Borrow<Q> + Eq + Hash + Clone,
+    Q: ToOwned<Owned = K> + Eq + Hash + ?Sized,
+{
+    match literals {
+        Some(literals) => {
+            let table = Rc::make_mut(table.get_or_insert_with(Default::default));
+            table.insert(key.to_owned(), literals);
+        }
+        None => {
+            let now_empty = match table {
+                Some(existing) if existing.contains_key(key) => {
+                    let existing = Rc::make_mut(existing);
+                    existing.remove(key);
+                    existing.is_empty()
+                }
+                _ => false,
+            };
+            if now_empty {
+                *table = None;
+            }
+        }
+    }
+}
+
 impl JVArray {
     pub fn new() -> Self {
-        JVArray(JVRaw::empty_array())
+        JVArray(JVRaw::empty_array(), None)
     }
     pub fn set(&mut self, i: i32, v: JV) {
+        set_literals(&mut self.1, &i, v.literals());
         self.0.array_set(i, v.into())
     }
     pub fn iter(&self) -> BorrowedArrayIterator<'_> {
@@ -85,14 +320,33 @@ impl JVArray {
     pub fn get(&self, i: i32) -> Option<JV> {
         if (0..self.len()).contains(&i) {
             let raw = self.0.array_get(i);
-            Some(
-                raw.try_into()
-                    .expect("JV should not have nested invalid value"),
-            )
+            let out: JV = raw
+                .try_into()
+                .expect("JV should not have nested invalid value");
+            let literals = self.1.as_ref().and_then(|literals| literals.get(&i));
+            Some(out.with_literals(literals))
         } else {
             None
         }
     }
+    pub fn insert(&mut self, i: i32, v: JV) {
+        let mut out = JVArray::new();
+        let before = self.iter().take(i as usize);
+        let after = self.iter().skip(i as usize);
+        for (j, x) in before.chain(std::iter::once(v)).chain(after).enumerate() {
+            out.set(j as i32, x);
+        }
+        *self = out;
+    }
+    pub fn remove(&mut self, i: i32) {
+        let mut out = JVArray::new();
+        let before = self.iter().take(i as usize);
+        let after = self.iter().skip(i as usize + 1);
+        for (j, x) in before.chain(after).enumerate() {
+            out.set(j as i32, x);
+        }
+        *self = out;
+    }
 }
 
 pub struct OwnedArrayIterator {
@@ -130,16 +384,29 @@ impl std::iter::IntoIterator for JVArray {
 }
 impl JVObject {
     pub fn new() -> Self {
-        JVObject(JVRaw::empty_object())
+        JVObject(JVRaw::empty_object(), None)
     }
     pub fn set(&mut self, k: &str, v: JV) {
+        set_literals(&mut self.1, k, v.literals());
         self.0.object_set(k, v.into())
     }
-    pub fn iter(&self) -> ObjectIterator {
-        self.0.object_iter()
+    pub fn get(&self, k: &str) -> Option<JV> {
+        // jq returns an invalid value for missing keys
+        let out: JV = self.0.object_get(k).try_into().ok()?;
+        Some(out.with_literals(self.child_literals(k)))
     }
-    pub fn values(&self) -> ObjectValuesIterator {
-        self.0.object_values()
+    pub fn delete(&mut self, k: &str) {
+        set_literals(&mut self.1, k, None);
+        self.0.object_delete(k)
+    }
+    pub fn iter(&self) -> ObjectIterator<'_> {
+        ObjectIterator {
+            inner: self.0.object_iter(),
+            literals: self.1.as_deref(),
+        }
+    }
+    pub fn values(&self) -> impl Iterator<Item = JV> + '_ {
+        self.iter().map(|(_, v)| v)
     }
     pub fn len(&self) -> i32 {
         self.0.object_len()
@@ -148,16 +415,63 @@ impl JVObject {
         self.len() == 0
     }
     pub fn into_empty_iter(self) -> OwnedObjectIterator {
-        self.0.into_empty_object_iter()
+        OwnedObjectIterator {
+            inner: self.0.into_empty_object_iter(),
+            literals: None,
+        }
+    }
+    fn child_literals(&self, k: &str) -> Option<&Literals> {
+        self.1.as_ref()?.get(k)
     }
 }
 impl std::iter::IntoIterator for JVObject {
     type Item = (JVString, JV);
     type IntoIter = OwnedObjectIterator;
     fn into_iter(self) -> Self::IntoIter {
-        self.0.into_object_iter()
+        OwnedObjectIterator {
+            inner: self.0.into_object_iter(),
+            literals: self.1,
+        }
+    }
+}
+
+pub struct ObjectIterator<'a> {
+    inner: jv_raw::ObjectIterator<'a>,
+    literals: Option<&'a ObjectLiterals>,
+}
+impl<'a> Iterator for ObjectIterator<'a> {
+    type Item = (&'a str, JV);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, v) = self.inner.next()?;
+        let literals = self.literals.and_then(|literals| literals.get(k));
+        Some((k, v.with_literals(literals)))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<'a> ExactSizeIterator for ObjectIterator<'a> {}
+
+#[derive(Clone)]
+pub struct OwnedObjectIterator {
+    inner: jv_raw::OwnedObjectIterator,
+    literals: Option<Rc<ObjectLiterals>>,
+}
+impl Iterator for OwnedObjectIterator {
+    type Item = (JVString, JV);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, v) = self.inner.next()?;
+        let literals = self
+            .literals
+            .as_ref()
+            .and_then(|literals| literals.get(k.value()));
+        Some((k, v.with_literals(literals)))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
     }
 }
+impl ExactSizeIterator for OwnedObjectIterator {}
 impl From<JVNull> for JV {
     fn from(x: JVNull) -> Self {
         JV::Null(x)
@@ -198,10 +512,10 @@ impl TryFrom<JVRaw> for JV {
                 .unwrap_or_else(|| "No error message".to_owned())),
             JVKind::Null => Ok(JVNull(raw).into()),
             JVKind::False | JVKind::True => Ok(JVBool(raw).into()),
-            JVKind::Number => Ok(JVNumber(raw).into()),
+            JVKind::Number => Ok(JVNumber(raw, None).into()),
             JVKind::String => Ok(JVString(raw).into()),
-            JVKind::Array => Ok(JVArray(raw).into()),
-            JVKind::Object => Ok(JVObject(raw).into()),
+            JVKind::Array => Ok(JVArray(raw, None).into()),
+            JVKind::Object => Ok(JVObject(raw, None).into()),
         }
     }
 }
@@ -210,7 +524,10 @@ impl From<&JV> for Value {
         match j {
             JV::Null(_) => Value::Null,
             JV::Bool(b) => b.value().into(),
-            JV::Number(x) => x.value().into(),
+            JV::Number(x) => match x.literal().and_then(|literal| literal.parse().ok()) {
+                Some(n) => Value::Number(n),
+                None => x.value().into(),
+            },
             JV::String(s) => s.value().into(),
             JV::Array(arr) => arr.iter().map(|x| Value::from(&x)).collect(),
             JV::Object(obj) => Value::Object(
@@ -223,9 +540,28 @@ impl From<&JV> for Value {
 }
 impl From<&Value> for JV {
     fn from(v: &Value) -> Self {
-        JVRaw::from_serde(v)
-            .try_into()
-            .expect("from_serde should not produce invalid value")
+        match v {
+            Value::Null => JVNull::new().into(),
+            Value::Bool(b) => JVBool::new(*b).into(),
+            Value::Number(n) => JVNumber::from_literal(&n.to_string())
+                .expect("Invalid number")
+                .into(),
+            Value::String(s) => JVString::new(s).into(),
+            Value::Array(xs) => {
+                let mut arr = JVArray::new();
+                for (i, x) in xs.iter().enumerate() {
+                    arr.set(i as i32, x.into());
+                }
+                arr.into()
+            }
+            Value::Object(obj) => {
+                let mut out = JVObject::new();
+                for (k, v) in obj {
+                    out.set(k, v.into());
+                }
+                out.into()
+            }
+        }
     }
 }
 impl<'a> From<&'a JV> for &'a JVRaw {
@@ -233,10 +569,10 @@ impl<'a> From<&'a JV> for &'a JVRaw {
         match j {
             &JV::Null(JVNull(ref out))
             | &JV::Bool(JVBool(ref out))
-            | &JV::Number(JVNumber(ref out))
+            | &JV::Number(JVNumber(ref out, _))
             | &JV::String(JVString(ref out))
-            | &JV::Array(JVArray(ref out))
-            | &JV::Object(JVObject(ref out)) => out,
+            | &JV::Array(JVArray(ref out, _))
+            | &JV::Object(JVObject(ref out, _)) => out,
         }
     }
 }
@@ -245,10 +581,10 @@ impl From<JV> for JVRaw {
         match j {
             JV::Null(JVNull(out))
             | JV::Bool(JVBool(out))
-            | JV::Number(JVNumber(out))
+            | JV::Number(JVNumber(out, _))
             | JV::String(JVString(out))
-            | JV::Array(JVArray(out))
-            | JV::Object(JVObject(out)) => out,
+            | JV::Array(JVArray(out, _))
+            | JV::Object(JVObject(out, _)) => out,
         }
     }
 }
@@ -293,12 +629,26 @@ impl<'de> Deserialize<'de> for JV {
 
             #[inline]
             fn visit_i64<E>(self, value: i64) -> Result<JV, E> {
-                Ok(JVNumber::new(value as f64).into())
+                Ok(integer_to_jv(value.into()))
             }
 
             #[inline]
             fn visit_u64<E>(self, value: u64) -> Result<JV, E> {
-                Ok(JVNumber::new(value as f64).into())
+                Ok(integer_to_jv(value.into()))
+            }
+
+            // Yaml has integers too big for 64 bits
+            fn visit_i128<E>(self, value: i128) -> Result<JV, E> {
+                Ok(integer_to_jv(value))
+            }
+
+            fn visit_u128<E>(self, value: u128) -> Result<JV, E> {
+                match i128::try_from(value) {
+                    Ok(value) => Ok(integer_to_jv(value)),
+                    Err(_) => Ok(JVNumber::from_literal(&value.to_string())
+                        .expect("Integers are valid numbers")
+                        .into()),
+                }
             }
 
             #[inline]
@@ -352,9 +702,22 @@ impl<'de> Deserialize<'de> for JV {
             where
                 V: MapAccess<'de>,
             {
+                let first_key = match visitor.next_key()? {
+                    Some(FirstKey::Number) => {
+                        let NumberLiteral(x) = visitor.next_value()?;
+                        return Ok(x.into());
+                    }
+                    Some(FirstKey::Key(key)) => Some(key),
+                    None => None,
+                };
                 let mut obj = JVObject::new();
+                if let Some(key) = first_key {
+                    let value = visitor.next_value()?;
+                    obj.set(&key, value);
+                }
 
-                while let Some((key, value)) = visitor.next_entry::<String, _>()? {
+                while let Some(key) = visitor.next_key::<String>()? {
+                    let value = visitor.next_value()?;
                     obj.set(&key, value);
                 }
 
@@ -366,6 +729,84 @@ impl<'de> Deserialize<'de> for JV {
     }
 }
 
+// The first key of a map, which tells numbers apart from objects without copying the key
+enum FirstKey {
+    Number,
+    Key(String),
+}
+impl<'de> Deserialize<'de> for FirstKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct FirstKeyVisitor;
+
+        impl<'de> Visitor<'de> for FirstKeyVisitor {
+            type Value = FirstKey;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string key")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<FirstKey, E> {
+                if value == NUMBER_TOKEN {
+                    Ok(FirstKey::Number)
+                } else {
+                    Ok(FirstKey::Key(value.to_owned()))
+                }
+            }
+
+            fn visit_string<E>(self, value: String) -> Result<FirstKey, E> {
+                if value == NUMBER_TOKEN {
+                    Ok(FirstKey::Number)
+                } else {
+                    Ok(FirstKey::Key(value))
+                }
+            }
+        }
+
+        deserializer.deserialize_str(FirstKeyVisitor)
+    }
+}
+
+// The literal after NUMBER_TOKEN, parsed without taking a copy of it
+struct NumberLiteral(JVNumber);
+impl<'de> Deserialize<'de> for NumberLiteral {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct NumberLiteralVisitor;
+
+        impl<'de> Visitor<'de> for NumberLiteralVisitor {
+            type Value = NumberLiteral;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a number literal")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<NumberLiteral, E> {
+                JVNumber::from_literal(value)
+                    .map(NumberLiteral)
+                    .ok_or_else(|| E::custom("invalid number"))
+            }
+        }
+
+        deserializer.deserialize_str(NumberLiteralVisitor)
+    }
+}
+
+fn integer_to_jv(value: i128) -> JV {
+    // Every integer this small is exactly representable as a double
+    if value.abs() <= 1 << 53 {
+        JVNumber::new(value as f64).into()
+    } else {
+        JVNumber::from_literal(&value.to_string())
+            .expect("Integers are valid numbers")
+            .into()
+    }
+}
+
 impl Serialize for JV {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -374,7 +815,7 @@ impl Serialize for JV {
         match self {
             JV::Null(_) => serializer.serialize_none(),
             JV::Bool(b) => serializer.serialize_bool(b.value()),
-            JV::Number(x) => serializer.serialize_f64(x.value()),
+            JV::Number(x) => serialize_number(x, serializer),
             JV::String(s) => serializer.serialize_str(s.value()),
             JV::Array(arr) => {
                 let mut seq = serializer.serialize_seq(Some(arr.len() as usize))?;
@@ -398,11 +839,37 @@ impl JV {
     pub fn parse_native(s: &str) -> Result<Self, String> {
         JVRaw::parse_native(s).try_into()
     }
+    fn literals(&self) -> Option<Literals> {
+        match self {
+            JV::Number(JVNumber(_, literal)) => literal.clone().map(Literals::Number),
+            JV::Array(JVArray(_, literals)) => literals.clone().map(Literals::Array),
+            JV::Object(JVObject(_, literals)) => literals.clone().map(Literals::Object),
+            _ => None,
+        }
+    }
+    // Puts back the literals of a value taken out of an array or object
+    fn with_literals(self, literals: Option<&Literals>) -> Self {
+        match (self, literals) {
+            (JV::Number(JVNumber(raw, _)), Some(Literals::Number(literal))) => {
+                JVNumber(raw, Some(literal.clone())).into()
+            }
+            (JV::Array(JVArray(raw, _)), Some(Literals::Array(literals))) => {
+                JVArray(raw, Some(literals.clone())).into()
+            }
+            (JV::Object(JVObject(raw, _)), Some(Literals::Object(literals))) => {
+                JVObject(raw, Some(literals.clone())).into()
+            }
+            (out, _) => out,
+        }
+    }
 }
 #[cfg(test)]
 mod tests {
-    use super::JV;
-    use crate::testing::arb_json;
+    use super::{round_trips, Decimal, JVNumber, JV};
+    use crate::{
+        jq::query::{run_jq_query, JQ},
+        testing::arb_json,
+    };
     use proptest::proptest;
     use serde_json::{json, value::Value};
     use std::convert::TryInto;
@@ -454,16 +921,119 @@ mod tests {
     proptest! {
         #[test]
         fn prop_jv_serialize(value in arb_json()) {
-            let jv :JV = (&value).into();
-            let s1 = serde_json::to_string(&jv)?;
-            let s2 = serde_json::to_string(&value)?;
-            assert_eq!(s1, s2);
-            // Distressingly, serde doesn't actually pass the roundtrip test. Gonna let this one go
-            // for now.
-            /*
-            let via_ser: Value = serde_json::from_str(&s2)?;
-            assert_eq!(value, via_ser, "String was : {:?}", s2);
-            */
+            // We write integers without a trailing ".0", so the strings won't always match what
+            // serde_json writes, but the values should.
+            let jv: JV = (&value).into();
+            let s = serde_json::to_string(&jv)?;
+            let roundtrip: JV = serde_json::from_str(&s)?;
+            assert_eq!(jv, roundtrip, "String was : {:?}", s);
+        }
+    }
+    #[test]
+    fn unit_number_literals() {
+        let tests = vec![
+            ("12345678901234567891", "12345678901234567891"),
+            ("-9007199254740993", "-9007199254740993"),
+            ("0.1000000000000000000001", "0.1000000000000000000001"),
+            ("1e400", "1e400"),
+            ("42", "42"),
+            ("42.0", "42"),
+            ("1.5e3", "1500"),
+            ("-0.25", "-0.25"),
+        ];
+        for (literal, expected) in tests {
+            let jv: JV = serde_json::from_str(literal).unwrap();
+            assert_eq!(serde_json::to_string(&jv).unwrap(), expected);
+            if let JV::Number(x) = &jv {
+                assert_eq!(x.to_string(), expected);
+            } else {
+                panic!("{} should parse as a number", literal);
+            }
+        }
+        // Literals inside arrays and objects are kept too, but jq only sees the doubles
+        let jv: JV = serde_json::from_str(r#"{"id": 1234567890123456789}"#).unwrap();
+        assert_eq!(
+            serde_json::to_string(&jv).unwrap(),
+            r#"{"id":1234567890123456789}"#
+        );
+        let mut prog = JQ::compile(".id").unwrap();
+        let results = run_jq_query(&[jv], &mut prog).unwrap();
+        assert_eq!(
+            serde_json::to_string(&results).unwrap(),
+            "[1234567890123456768]"
+        );
+    }
+    #[test]
+    fn unit_number_literals_per_value() {
+        // An exact and an inexact literal of the same double
+        let jv: JV = serde_json::from_str("[9007199254740993, 9007199254740992]").unwrap();
+        assert_eq!(
+            serde_json::to_string(&jv).unwrap(),
+            "[9007199254740993,9007199254740992]"
+        );
+        let jv: JV = serde_json::from_str("[0.1000000000000000000001, 0.1]").unwrap();
+        assert_eq!(
+            serde_json::to_string(&jv).unwrap(),
+            "[0.1000000000000000000001,0.1]"
+        );
+        let jv: JV = serde_json::from_str("0.1").unwrap();
+        assert_eq!(serde_json::to_string(&jv).unwrap(), "0.1");
+        // Numbers jq computes don't get a literal, even when they land on the same double
+        let jv: JV = serde_json::from_str(r#"{"a": 12345678901234567891, "b": 1e400}"#).unwrap();
+        let tests = vec![
+            (".a + 1", "[1.2345678901234567e19]"),
+            ("infinite", "[1.7976931348623157e308]"),
+            (
+                "[.b, infinite]",
+                "[[1.7976931348623157e308,1.7976931348623157e308]]",
+            ),
+        ];
+        for (query, expected) in tests {
+            let mut prog = JQ::compile(query).unwrap();
+            let results = run_jq_query(std::slice::from_ref(&jv), &mut prog).unwrap();
+            assert_eq!(
+                serde_json::to_string(&results).unwrap(),
+                expected,
+                "{}",
+                query
+            );
+        }
+    }
+    #[test]
+    fn unit_number_literals_edited() {
+        // Literals move along with their values when arrays and objects change
+        let jv: JV =
+            serde_json::from_str(r#"{"a": [1, 12345678901234567891], "b": 9007199254740993}"#)
+                .unwrap();
+        let mut obj = match jv {
+            JV::Object(obj) => obj,
+            _ => panic!("Should be an object"),
+        };
+        let mut arr = match obj.get("a") {
+            Some(JV::Array(arr)) => arr,
+            _ => panic!("Should be an array"),
+        };
+        arr.remove(0);
+        arr.insert(1, JVNumber::new(2.0).into());
+        obj.set("a", arr.into());
+        obj.set(
+            "c",
+            serde_json::from_str("0.1000000000000000000001").unwrap(),
+        );
+        obj.delete("b");
+        assert_eq!(
+            serde_json::to_string(&JV::from(obj)).unwrap(),
+            r#"{"a":[12345678901234567891,2],"c":0.1000000000000000000001}"#
+        );
+    }
+    proptest! {
+        #[test]
+        fn prop_number_literal_round_trips(
+            literal in "-?(0|[1-9][0-9]{0,19})(\\.[0-9]{1,20})?([eE][+-]?[0-9]{1,3})?"
+        ) {
+            let x: f64 = literal.parse().unwrap();
+            let expected = Decimal::parse(&x.to_string()) == Decimal::parse(&literal);
+            assert_eq!(round_trips(&literal, x), expected, "{}", literal);
         }
     }
 }
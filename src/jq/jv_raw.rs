@@ -4,11 +4,11 @@ use jq_sys::{
     jv_get_kind, jv_invalid_get_msg, jv_invalid_has_msg, jv_kind_JV_KIND_ARRAY,
     jv_kind_JV_KIND_FALSE, jv_kind_JV_KIND_INVALID, jv_kind_JV_KIND_NULL, jv_kind_JV_KIND_NUMBER,
     jv_kind_JV_KIND_OBJECT, jv_kind_JV_KIND_STRING, jv_kind_JV_KIND_TRUE, jv_null, jv_number,
-    jv_number_value, jv_object, jv_object_iter, jv_object_iter_key, jv_object_iter_next,
-    jv_object_iter_valid, jv_object_iter_value, jv_object_length, jv_object_set, jv_parse_sized,
-    jv_string_length_bytes, jv_string_sized, jv_string_value,
+    jv_number_value, jv_object, jv_object_delete, jv_object_get, jv_object_iter,
+    jv_object_iter_key, jv_object_iter_next, jv_object_iter_valid, jv_object_iter_value,
+    jv_object_length, jv_object_set, jv_parse_sized, jv_string_length_bytes, jv_string_sized,
+    jv_string_value,
 };
-use serde_json::value::Value;
 use std::{convert::TryInto, fmt, iter::FromIterator, mem::forget, os::raw::c_char, slice, str};
 
 #[repr(transparent)]
@@ -81,6 +81,20 @@ impl JVRaw {
         self.ptr =
             unsafe { jv_object_set(self.ptr, key.unwrap_without_drop(), v.unwrap_without_drop()) };
     }
+    pub fn object_get(&self, k: &str) -> JVRaw {
+        let key = JVRaw::string(k);
+        let ptr = unsafe {
+            jv_object_get(
+                self.clone().unwrap_without_drop(),
+                key.unwrap_without_drop(),
+            )
+        };
+        JVRaw { ptr }
+    }
+    pub fn object_delete(&mut self, k: &str) {
+        let key = JVRaw::string(k);
+        self.ptr = unsafe { jv_object_delete(self.ptr, key.unwrap_without_drop()) };
+    }
     pub fn bool(b: bool) -> Self {
         JVRaw {
             ptr: unsafe { jv_bool(b.into()) },
@@ -104,22 +118,6 @@ impl JVRaw {
             ptr: unsafe { jv_null() },
         }
     }
-    // from_serde is attached to JVRaw so we don't have to strip off the outer layer when stuffing
-    // things into arrays.
-    pub fn from_serde(v: &Value) -> Self {
-        match v {
-            Value::Null => JVRaw::null(),
-            Value::Bool(b) => JVRaw::bool(*b),
-            Value::Number(n) => JVRaw::number(n.as_f64().expect("Non-f64 number")),
-            Value::String(s) => JVRaw::string(s),
-            Value::Array(xs) => xs.iter().map(JVRaw::from_serde).collect(),
-            Value::Object(obj) => obj
-                .iter()
-                .map(|(k, v)| (k.as_str(), JVRaw::from_serde(v)))
-                .collect(),
-        }
-    }
-
     pub fn get_kind(&self) -> JVKind {
         let raw_kind = unsafe { jv_get_kind(self.ptr) };
         #[allow(non_upper_case_globals)]
@@ -178,14 +176,6 @@ impl JVRaw {
             obj: self,
         }
     }
-    pub fn object_values(&self) -> ObjectValuesIterator {
-        let i = unsafe { jv_object_iter(self.ptr) };
-        ObjectValuesIterator {
-            remaining: self.object_len() as usize,
-            i,
-            obj: self,
-        }
-    }
     pub fn array_len(&self) -> i32 {
         unsafe { jv_array_length(self.clone().unwrap_without_drop()) }
     }
@@ -317,29 +307,3 @@ impl Iterator for OwnedObjectIterator {
 }
 
 impl<'a> ExactSizeIterator for OwnedObjectIterator {}
-
-pub struct ObjectValuesIterator<'a> {
-    remaining: usize,
-    i: i32,
-    obj: &'a JVRaw,
-}
-
-impl<'a> Iterator for ObjectValuesIterator<'a> {
-    type Item = JV;
-    fn next(&mut self) -> Option<Self::Item> {
-        if unsafe { jv_object_iter_valid(self.obj.ptr, self.i) } == 0 {
-            return None;
-        }
-        let v = JVRaw {
-            ptr: unsafe { jv_object_iter_value(self.obj.ptr, self.i) },
-        };
-        self.i = unsafe { jv_object_iter_next(self.obj.ptr, self.i) };
-        self.remaining -= 1;
-        Some(v.try_into().expect("Object should not contain invalid JV"))
-    }
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.remaining, Some(self.remaining))
-    }
-}
-
-impl<'a> ExactSizeIterator for ObjectValuesIterator<'a> {}
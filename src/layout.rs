@@ -4,6 +4,8 @@ pub struct JexLayout {
     pub tree: Option<Rect>,
     pub left: Rect,
     pub right: Rect,
+    // Where the cursor is in the focused pane
+    pub status: Rect,
     pub query: Rect,
 }
 
@@ -11,7 +13,14 @@ impl JexLayout {
     pub fn new(size: Rect, show_tree: bool) -> JexLayout {
         let vchunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+            .constraints(
+                [
+                    Constraint::Min(0),
+                    Constraint::Length(1),
+                    Constraint::Length(1),
+                ]
+                .as_ref(),
+            )
             .split(size);
         if show_tree {
             let tree_split = Layout::default()
@@ -26,7 +35,8 @@ impl JexLayout {
                 tree: Some(tree_split[0]),
                 left: views[0],
                 right: views[1],
-                query: vchunks[1],
+                status: vchunks[1],
+                query: vchunks[2],
             }
         } else {
             let views = Layout::default()
@@ -37,7 +47,8 @@ impl JexLayout {
                 tree: None,
                 left: views[0],
                 right: views[1],
-                query: vchunks[1],
+                status: vchunks[1],
+                query: vchunks[2],
             }
         }
     }
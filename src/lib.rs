@@ -1,8 +1,18 @@
 pub mod app;
+pub mod clipboard;
 pub mod cursor;
+pub mod diff;
+pub mod edit;
+pub mod export;
+pub mod fold;
+pub mod format;
 pub mod jq;
 pub mod layout;
 pub mod lines;
+pub mod loader;
+pub mod save;
+pub mod search;
+pub mod session;
 #[cfg(test)]
 mod testing;
 pub mod view_tree;
@@ -1,11 +1,14 @@
-use crate::jq::jv::JVString;
+use crate::{
+    diff::DiffKind,
+    jq::jv::{JVNumber, JVString},
+};
 use std::{cell::RefCell, matches, ops::Range, rc::Rc};
 use tui::{
     style::{Color, Modifier, Style},
     text::{Span, Spans},
 };
 use unicode_general_category::{get_general_category, GeneralCategory};
-use unicode_width::UnicodeWidthChar;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Leaf {
@@ -19,7 +22,7 @@ pub struct Leaf {
 pub enum LeafContent {
     Null,
     Bool(bool),
-    Number(f64),
+    Number(JVNumber),
     String(JVString),
     FoldedArray(usize),
     ArrayStart,
@@ -211,6 +214,27 @@ impl UnstyledSpans {
             .collect();
         v.into()
     }
+    // Whether the character at this column is part of the value itself (rather than its key, or a
+    // comma), which for arrays and objects means the bracket.
+    pub fn is_value_at(&self, column: u16) -> bool {
+        let mut start = 0;
+        for span in self.content.iter() {
+            let end = start + span.text.width();
+            if (start..end).contains(&(column as usize)) {
+                return span.style_type == StyleType::Highlightable;
+            }
+            start = end;
+        }
+        false
+    }
+    // Colors the value part of the line to show how it differs from the other side of a diff
+    pub fn mark(&mut self, kind: DiffKind) {
+        for span in self.content.iter_mut() {
+            if span.style_type == StyleType::Highlightable {
+                span.style_type = kind.into();
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -224,14 +248,35 @@ pub enum StyleType {
     Unhighlightable,
     Highlightable,
     Background,
+    // Highlightable, but marked by a diff
+    Added,
+    Removed,
+    Changed,
 }
 
 impl StyleType {
     fn to_style(self, is_cursor: bool) -> Style {
-        match self {
-            StyleType::Highlightable if is_cursor => Style::default().bg(Color::Blue),
+        let style = match self {
             StyleType::Unhighlightable | StyleType::Highlightable => Style::default(),
-            StyleType::Background => Style::default().add_modifier(Modifier::DIM),
+            StyleType::Background => return Style::default().add_modifier(Modifier::DIM),
+            StyleType::Added => Style::default().fg(Color::Green),
+            StyleType::Removed => Style::default().fg(Color::Red),
+            StyleType::Changed => Style::default().fg(Color::Yellow),
+        };
+        match self {
+            StyleType::Unhighlightable => style,
+            _ if is_cursor => style.bg(Color::Blue),
+            _ => style,
+        }
+    }
+}
+
+impl From<DiffKind> for StyleType {
+    fn from(kind: DiffKind) -> Self {
+        match kind {
+            DiffKind::Added => StyleType::Added,
+            DiffKind::Removed => StyleType::Removed,
+            DiffKind::Changed => StyleType::Changed,
         }
     }
 }
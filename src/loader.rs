@@ -0,0 +1,392 @@
+use crate::{
+    format::{decompress_detecting, describe, is_bad_line, Format, Location},
+    jq::jv::JV,
+};
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::VecDeque,
+    io,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// How long the loader collects values before handing them over. Every batch means copying the
+// top level values and running queries on the new ones, so we don't want to do it for every value.
+const BATCH_INTERVAL: Duration = Duration::from_millis(200);
+// How much of the input we hang on to for showing where a parse error is
+const TAIL_BYTES: usize = 64 * 1024;
+// Lines shown before the one with the error
+const EXCERPT_CONTEXT: usize = 2;
+// Characters shown around the error, for when it's in the middle of a long line
+const EXCERPT_WIDTH: usize = 60;
+// The panic hook leaves the terminal alone for this thread, since its panics are shown as load
+// errors.
+pub const LOADER_THREAD: &str = "loader";
+
+// JVs aren't Send because jq's reference counts aren't atomic, and neither are the Rcs holding
+// number literals. Sending a batch is still safe as long as every reference into its values moves
+// with it, since then no count is ever touched by two threads. That holds because:
+// - the values are parsed from scratch on the loader thread, and nothing there is shared with
+//   values from any other thread;
+// - a value goes straight from the parser into the batch, without being cloned, and the loader
+//   keeps nothing that points into it (the tail and any errors are plain bytes and strings);
+// - the batch is moved into the channel whole, and the loader never looks at it again.
+// Anything that clones or keeps a JV on the loader thread before the handoff breaks this.
+struct Batch(Vec<JV>);
+unsafe impl Send for Batch {}
+
+enum Message {
+    Values(Batch),
+    Error(LoadError),
+    // A line that didn't parse, and was skipped
+    Skipped(String),
+    // Sent first if the input turned out to be compressed
+    Compressed,
+}
+
+// Parses json on a background thread, so we can show the first values while the rest loads.
+pub struct Loader {
+    receiver: mpsc::Receiver<Message>,
+    // Joined once the channel closes, to tell a finished load from a crashed one
+    thread: Option<thread::JoinHandle<()>>,
+    bytes_read: Arc<AtomicU64>,
+    total_bytes: Option<u64>,
+    skipped: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct LoaderUpdate {
+    pub values: Vec<JV>,
+    pub error: Option<LoadError>,
+    // Every skipped line, once loading is done
+    pub skipped: Vec<String>,
+    pub compressed: bool,
+    pub done: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadError {
+    pub message: String,
+    pub location: Option<Location>,
+    // The numbered lines leading up to the error, cut down to the part around the error's column
+    pub excerpt: Vec<(usize, String)>,
+    // Where the error's column ended up in the last line of the excerpt
+    pub excerpt_column: usize,
+}
+
+impl Loader {
+    pub fn spawn<R: io::Read + Send + 'static>(
+        r: R,
+        format: Format,
+        total_bytes: Option<u64>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let bytes_read = Arc::new(AtomicU64::new(0));
+        let counter = CountingReader {
+            inner: r,
+            count: bytes_read.clone(),
+        };
+        // The count is of the bytes before decompression, so that it matches total_bytes
+        let spawned = thread::Builder::new().name(LOADER_THREAD.to_string());
+        let thread = spawned.spawn(move || match decompress_detecting(counter) {
+            Ok((r, compressed)) => {
+                if compressed {
+                    let _ = sender.send(Message::Compressed);
+                }
+                let tail = Rc::new(RefCell::new(Tail::default()));
+                let r = TailReader {
+                    inner: r,
+                    tail: tail.clone(),
+                };
+                load(format.values(io::BufReader::new(r)), &tail, sender)
+            }
+            Err(err) => {
+                let _ = sender.send(Message::Error(LoadError::new(&err, &Tail::default())));
+            }
+        });
+        Loader {
+            receiver,
+            thread: Some(thread.expect("Failed to start the loader thread")),
+            bytes_read,
+            total_bytes,
+            skipped: Vec::new(),
+        }
+    }
+    // Takes everything that's been parsed since the last call, without waiting for more.
+    pub fn poll(&mut self) -> LoaderUpdate {
+        let mut update = LoaderUpdate::default();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(Message::Values(Batch(values))) => update.values.extend(values),
+                Ok(Message::Error(err)) => update.error = Some(err),
+                Ok(Message::Skipped(line)) => self.skipped.push(line),
+                Ok(Message::Compressed) => update.compressed = true,
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    let result = self.thread.take().map_or(Ok(()), |thread| thread.join());
+                    if let Err(payload) = result {
+                        update.error = Some(LoadError::crashed(payload));
+                    }
+                    update.done = true;
+                    update.skipped = std::mem::take(&mut self.skipped);
+                    break;
+                }
+            }
+        }
+        update
+    }
+    pub fn progress(&self) -> String {
+        let bytes_read = self.bytes_read.load(Ordering::Relaxed);
+        let progress = match self.total_bytes {
+            Some(total) if total > 0 => format!("Loading: {}%", bytes_read * 100 / total),
+            _ => format!("Loading: {:.1} MB", bytes_read as f64 / 1_000_000.0),
+        };
+        match self.skipped.len() {
+            0 => progress,
+            1 => format!("{} (skipped 1 bad line)", progress),
+            n => format!("{} (skipped {} bad lines)", progress, n),
+        }
+    }
+}
+
+impl LoadError {
+    fn new(err: &io::Error, tail: &Tail) -> Self {
+        let (message, location) = describe(err);
+        let (excerpt, excerpt_column) = match location {
+            Some(location) => tail.excerpt(location),
+            None => (Vec::new(), 0),
+        };
+        LoadError {
+            message,
+            location,
+            excerpt,
+            excerpt_column,
+        }
+    }
+    // The loader panicked, so whatever it hadn't sent yet is lost
+    fn crashed(payload: Box<dyn Any + Send>) -> Self {
+        let reason = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "unknown error".to_string(),
+            },
+        };
+        LoadError {
+            message: format!("The loader crashed: {}", reason),
+            location: None,
+            excerpt: Vec::new(),
+            excerpt_column: 0,
+        }
+    }
+}
+
+fn load<I: Iterator<Item = io::Result<JV>>>(
+    values: I,
+    tail: &RefCell<Tail>,
+    sender: mpsc::Sender<Message>,
+) {
+    let mut batch = Vec::new();
+    let mut last_sent = Instant::now();
+    for value in values {
+        match value {
+            Ok(value) => batch.push(value),
+            Err(err) if is_bad_line(&err) => {
+                let _ = sender.send(Message::Skipped(err.to_string()));
+            }
+            Err(err) => {
+                let values = std::mem::take(&mut batch);
+                let _ = sender.send(Message::Values(Batch(values)));
+                let error = LoadError::new(&err, &tail.borrow());
+                let _ = sender.send(Message::Error(error));
+                return;
+            }
+        }
+        if last_sent.elapsed() >= BATCH_INTERVAL {
+            let values = std::mem::take(&mut batch);
+            if sender.send(Message::Values(Batch(values))).is_err() {
+                // The viewer has gone away
+                return;
+            }
+            last_sent = Instant::now();
+        }
+    }
+    let _ = sender.send(Message::Values(Batch(batch)));
+}
+
+struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: io::Read> io::Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+// The end of the input read so far
+#[derive(Default)]
+struct Tail {
+    bytes: VecDeque<u8>,
+    // Lines that have been dropped from the start of bytes
+    dropped_lines: usize,
+}
+
+impl Tail {
+    fn push(&mut self, buf: &[u8]) {
+        self.bytes.extend(buf);
+        if self.bytes.len() > TAIL_BYTES {
+            let excess = self.bytes.len() - TAIL_BYTES;
+            self.dropped_lines += self.bytes.drain(..excess).filter(|&b| b == b'\n').count();
+        }
+    }
+    fn excerpt(&self, location: Location) -> (Vec<(usize, String)>, usize) {
+        let bytes: Vec<u8> = self.bytes.iter().copied().collect();
+        let text = String::from_utf8_lossy(&bytes);
+        // Long lines are cut down to a window around the error, the same for every line so that
+        // they still line up
+        let start = location.column.saturating_sub(EXCERPT_WIDTH / 2 + 1);
+        let first_line = location.line.saturating_sub(EXCERPT_CONTEXT);
+        let excerpt = text
+            .split('\n')
+            .enumerate()
+            .map(|(i, line)| (i + self.dropped_lines + 1, line))
+            .skip_while(|&(number, _)| number < first_line)
+            .take_while(|&(number, _)| number <= location.line)
+            .map(|(number, line)| {
+                let line = line.trim_end_matches('\r');
+                (
+                    number,
+                    line.chars().skip(start).take(EXCERPT_WIDTH).collect(),
+                )
+            })
+            .collect();
+        (excerpt, location.column - start)
+    }
+}
+
+struct TailReader<R> {
+    inner: R,
+    tail: Rc<RefCell<Tail>>,
+}
+
+impl<R: io::Read> io::Read for TailReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.tail.borrow_mut().push(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LoadError, Loader};
+    use crate::{
+        format::{Format, Location},
+        jq::jv::JV,
+    };
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+    fn load_all(format: Format, input: &'static str) -> (Vec<Value>, Option<LoadError>) {
+        let mut loader = Loader::spawn(input.as_bytes(), format, Some(input.len() as u64));
+        let mut values: Vec<JV> = Vec::new();
+        let mut error = None;
+        loop {
+            let update = loader.poll();
+            assert!(update.skipped.is_empty());
+            values.extend(update.values);
+            error = error.or(update.error);
+            if update.done {
+                break;
+            }
+            std::thread::yield_now();
+        }
+        assert_eq!(loader.progress(), "Loading: 100%");
+        (values.iter().map(Value::from).collect(), error)
+    }
+    #[test]
+    fn unit_loader() {
+        let (values, error) = load_all(Format::Json, "{\"a\": 1.0}\n[2.0]\n\"three\"\n");
+        assert_eq!(
+            values,
+            vec![json!({"a": 1.0}), json!([2.0]), json!("three")]
+        );
+        assert_eq!(error, None);
+    }
+    #[test]
+    fn unit_loader_error() {
+        let (values, error) = load_all(Format::Json, "{\"a\": 1.0}\n[2.0 x]\n[3.0]\n");
+        assert_eq!(values, vec![json!({"a": 1.0})]);
+        let expected = LoadError {
+            message: "expected `,` or `]`".to_string(),
+            location: Some(Location { line: 2, column: 6 }),
+            excerpt: vec![(1, "{\"a\": 1.0}".to_string()), (2, "[2.0 x]".to_string())],
+            excerpt_column: 6,
+        };
+        assert_eq!(error, Some(expected));
+    }
+    #[test]
+    fn unit_loader_skip_bad_lines() {
+        let input = "{\"a\": 1.0}\n{\"a\" 2.0}\n\n[3.0]\n[\n";
+        let mut loader = Loader::spawn(input.as_bytes(), Format::Json.skip_bad_lines(), None);
+        let mut values: Vec<JV> = Vec::new();
+        let skipped = loop {
+            let update = loader.poll();
+            values.extend(update.values);
+            assert_eq!(update.error, None);
+            if update.done {
+                break update.skipped;
+            }
+            std::thread::yield_now();
+        };
+        let values: Vec<Value> = values.iter().map(Value::from).collect();
+        assert_eq!(values, vec![json!({"a": 1.0}), json!([3.0])]);
+        assert_eq!(
+            skipped,
+            vec![
+                "line 2, column 6: expected `:`",
+                "line 5, column 1: EOF while parsing a list",
+            ]
+        );
+    }
+    #[test]
+    fn unit_loader_crash() {
+        // Hands over one value, then panics
+        struct Crashing(&'static [u8]);
+        impl std::io::Read for Crashing {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0.is_empty() {
+                    panic!("disk on fire");
+                }
+                std::io::Read::read(&mut self.0, buf)
+            }
+        }
+        let mut loader = Loader::spawn(Crashing(b"[1.0]\n"), Format::Json, None);
+        let mut error = None;
+        loop {
+            let update = loader.poll();
+            error = error.or(update.error);
+            if update.done {
+                break;
+            }
+            std::thread::yield_now();
+        }
+        let error = error.expect("A crash should be reported");
+        assert_eq!(error.message, "The loader crashed: disk on fire");
+    }
+    #[test]
+    fn unit_loader_yaml() {
+        let (values, error) = load_all(Format::Yaml, "a: 1.5\n---\n- b\n");
+        assert_eq!(values, vec![json!({"a": 1.5}), json!(["b"])]);
+        assert_eq!(error, None);
+    }
+}
@@ -1,20 +1,45 @@
 use argh::FromArgs;
 use crossterm::{
     event,
-    event::KeyCode,
+    event::{DisableMouseCapture, EnableMouseCapture, KeyCode, MouseButton, MouseEvent},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use jex::{
     app::{App, AppRenderMode, Focus},
-    cursor::GlobalCursor,
+    clipboard::{copy_text, Clipboard, CopyKind},
+    cursor::{CursorFrame, GlobalCursor},
+    edit::Edit,
+    export,
+    fold::FoldRule,
+    format::{
+        decompress, describe, is_bad_line, is_compressed_path, BytesEncoding, Format, JsonLayout,
+        Location,
+    },
+    jq::{
+        jv::JV,
+        query::{run_jq_query, JQ},
+    },
     layout::JexLayout,
+    loader::LOADER_THREAD,
+    save,
+    search::Search,
+    session::Session,
     view_tree::View,
 };
 use log::debug;
-use regex::Regex;
 use simplelog::WriteLogger;
-use std::{default::Default, fs, fs::File, io, io::Write, panic};
+use std::{
+    default::Default,
+    fs,
+    fs::File,
+    io,
+    io::{Read, Write},
+    panic,
+    path::Path,
+    process, thread,
+    time::Duration,
+};
 use tui::{
     backend::CrosstermBackend,
     layout::Rect,
@@ -41,8 +66,56 @@ struct Args {
     #[argh(option)]
     #[argh(description = "logging output file")]
     log_path: Option<String>,
+    #[argh(option)]
+    #[argh(description = "run a jq query without opening the viewer, and print the results")]
+    query: Option<String>,
+    #[argh(switch, short = 'c')]
+    #[argh(description = "with --query, print each result on a single line")]
+    compact_output: bool,
+    #[argh(switch, short = 'r')]
+    #[argh(description = "with --query, print string results without quotes")]
+    raw_output: bool,
+    #[argh(option)]
+    #[argh(description = "session file to restore from (if it exists) and save to on exit")]
+    session: Option<String>,
+    #[argh(option)]
+    #[argh(description = "json file to compare against, shown in the right pane")]
+    diff: Option<String>,
+    #[argh(option)]
+    #[argh(
+        description = "input format: json, json5, yaml, csv, tsv, msgpack or cbor (guessed from the file extension by default)"
+    )]
+    format: Option<Format>,
+    #[argh(switch)]
+    #[argh(description = "for csv and tsv, read numbers, booleans and empty fields as json types")]
+    infer_types: bool,
+    #[argh(switch)]
+    #[argh(description = "accept comments, trailing commas and the rest of JSON5 in json input")]
+    lenient: bool,
+    #[argh(switch)]
+    #[argh(
+        description = "for newline-delimited json, skip lines that don't parse instead of stopping at them"
+    )]
+    skip_bad_lines: bool,
+    #[argh(switch)]
+    #[argh(description = "when saving over a file, keep the old one as <file>.bak")]
+    backup: bool,
+    #[argh(option)]
+    #[argh(
+        description = "for msgpack and cbor, how to show byte strings: base64 (default) or hex"
+    )]
+    bytes: Option<BytesEncoding>,
+    #[argh(option)]
+    #[argh(
+        description = "shell command to copy with, given the text on stdin (instead of OSC 52)"
+    )]
+    copy_command: Option<String>,
+    #[argh(option)]
+    #[argh(description = "file to copy to (instead of OSC 52)")]
+    copy_file: Option<String>,
     #[argh(positional)]
-    json_path: String,
+    #[argh(description = "json file to open (reads stdin if omitted or \"-\")")]
+    json_path: Option<String>,
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
@@ -100,7 +173,6 @@ struct BenchMode {}
 //   * Allow copying descendents onto another root, so you if you want to modify a tree's root you
 // can do so by making a new root and then copying over the descendents
 // * Lightweight error messages (no search results, can't fold a leaf, can't edit a non-leaf)
-// * Diffs
 //
 // Rendering pipeline:
 // * Vec<JV>
@@ -118,8 +190,8 @@ fn main() -> Result<(), io::Error> {
     let args: Args = argh::from_env();
     init_logging(&args);
     match args.mode {
-        Mode::Normal(_) => run(args.json_path),
-        Mode::Bench(_) => bench(args.json_path),
+        Mode::Normal(_) => run_normal(&args),
+        Mode::Bench(_) => bench(args.json_path.as_deref()),
     }
 }
 
@@ -127,7 +199,43 @@ fn main() -> Result<(), io::Error> {
 fn main() -> Result<(), io::Error> {
     let args: Args = argh::from_env();
     init_logging(&args);
-    run(args.json_path)
+    run_normal(&args)
+}
+
+fn run_normal(args: &Args) -> Result<(), io::Error> {
+    match &args.query {
+        Some(query) => batch(args, query),
+        None => run(args),
+    }
+}
+
+fn clipboard(args: &Args) -> Clipboard {
+    match (&args.copy_command, &args.copy_file) {
+        (Some(command), _) => Clipboard::Command(command.clone()),
+        (None, Some(path)) => Clipboard::File(path.clone()),
+        (None, None) => Clipboard::Osc52,
+    }
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Ok(metadata) = fs::metadata(path) {
+        let mut permissions = metadata.permissions();
+        permissions.set_mode(permissions.mode() | 0o111);
+        let _ = fs::set_permissions(path, permissions);
+    }
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) {}
+
+// Asks a yes or no question on the query line. Anything but yes is no.
+fn confirm(rl: &mut rustyline::Editor<()>, question: &str) -> bool {
+    match rl.readline(&format!("{} [y/N] ", question)) {
+        Ok(answer) => matches!(answer.trim(), "y" | "Y" | "yes"),
+        Err(_) => false,
+    }
 }
 
 fn init_logging(args: &Args) {
@@ -167,47 +275,230 @@ fn force_draw<B: tui::backend::Backend, F: FnMut(&mut Frame<B>)>(
     terminal.backend_mut().draw(updates.into_iter())
 }
 
+const STDIN_NAME: &str = "<stdin>";
+// How long typing has to pause before the query being edited is rerun
+const QUERY_PREVIEW_DELAY_MS: i32 = 150;
+// How often to check for newly loaded json while waiting for input
+const LOADER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// How many lines one notch of the mouse wheel scrolls
+const WHEEL_LINES: usize = 3;
+
+// Opens the json to view. With no path (or "-") the json is read from stdin, and the terminal is
+// reattached to stdin so that the UI can still take input.
+fn open_input(json_path: Option<&str>) -> io::Result<(Box<dyn Read + Send>, String)> {
+    match json_path {
+        None | Some("-") => {
+            let stdin = take_stdin()?;
+            Ok((Box::new(io::BufReader::new(stdin)), STDIN_NAME.to_string()))
+        }
+        Some(path) => {
+            let f = fs::File::open(path)?;
+            Ok((Box::new(io::BufReader::new(f)), path.to_string()))
+        }
+    }
+}
+
+// An explicit format wins, then the file's extension. Anything else is json.
+fn input_format(args: &Args, json_path: Option<&str>) -> Format {
+    let mut format = args
+        .format
+        .or_else(|| json_path.and_then(Format::from_path))
+        .unwrap_or(Format::Json);
+    if args.infer_types {
+        format = format.infer_types();
+    }
+    if args.lenient {
+        format = format.lenient();
+    }
+    if args.skip_bad_lines {
+        format = format.skip_bad_lines();
+    }
+    if let Some(bytes) = args.bytes {
+        format = format.with_bytes(bytes);
+    }
+    format
+}
+
+#[cfg(unix)]
+fn take_stdin() -> io::Result<File> {
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    if unsafe { libc::isatty(libc::STDIN_FILENO) } == 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "No json file given and stdin is a terminal",
+        ));
+    }
+    // Both crossterm and rustyline read keypresses from stdin, so we move the piped input to a new
+    // file descriptor and put the terminal in its place.
+    let tty = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")?;
+    unsafe {
+        let input = libc::dup(libc::STDIN_FILENO);
+        if input < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(File::from_raw_fd(input))
+    }
+}
+
+#[cfg(not(unix))]
+fn take_stdin() -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "Reading json from stdin is only supported on unix",
+    ))
+}
+
 struct DeferRestoreTerminal {}
 
 impl Drop for DeferRestoreTerminal {
     fn drop(&mut self) {
         disable_raw_mode().expect("Failed to disable raw mode");
-        execute!(io::stdout(), LeaveAlternateScreen).expect("Failed to leave alternate screen");
+        execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen)
+            .expect("Failed to leave alternate screen");
     }
 }
 
-fn run(json_path: String) -> Result<(), io::Error> {
+// Rustyline would read mouse events as typed text, so mouse capture is off while it's reading.
+struct PauseMouseCapture {}
+
+impl PauseMouseCapture {
+    fn new() -> Self {
+        execute!(io::stdout(), DisableMouseCapture).expect("Failed to disable mouse capture");
+        PauseMouseCapture {}
+    }
+}
+
+impl Drop for PauseMouseCapture {
+    fn drop(&mut self) {
+        execute!(io::stdout(), EnableMouseCapture).expect("Failed to enable mouse capture");
+    }
+}
+
+fn run(args: &Args) -> Result<(), io::Error> {
+    let session_path = args.session.as_deref();
+    let diff_path = args.diff.as_deref();
+    let clipboard = clipboard(args);
+    let backup = args.backup;
+    // The session's folds and cursors and the diff both need all of the json, so they wait until
+    // it's loaded
+    let mut session = match session_path {
+        Some(path) if Path::new(path).exists() => Some(Session::load(path)?),
+        _ => None,
+    };
+    // An explicit json path takes priority over the one recorded in the session
+    let json_path = args
+        .json_path
+        .as_deref()
+        .or_else(|| session.as_ref()?.source.as_deref());
+    let source = match json_path {
+        None | Some("-") => None,
+        Some(path) => Some(fs::canonicalize(path)?.to_string_lossy().into_owned()),
+    };
+    // Exported scripts read the file by the path it was given as, so they work from a checkout
+    let export_input = json_path.map(str::to_string);
+    let format = input_format(args, json_path);
+    let (r, name) = open_input(json_path)?;
+    // Read before taking over the terminal, so a bad file fails like the main one would
+    let mut diff_values = match diff_path {
+        Some(path) => {
+            let f = io::BufReader::new(decompress(fs::File::open(path)?)?);
+            let values = Format::from_path(path).unwrap_or(format).read(f)?;
+            Some((values, path.to_string()))
+        }
+        None => None,
+    };
     enable_raw_mode().expect("Failed to enter raw mode");
 
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen).expect("Failed to enter alternate screen");
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)
+        .expect("Failed to enter alternate screen");
     let default_panic_handler = panic::take_hook();
     panic::set_hook(Box::new(move |p| {
+        // The viewer keeps running, and shows the loader's panic as a load error
+        if thread::current().name() == Some(LOADER_THREAD) {
+            return;
+        }
         disable_raw_mode().expect("Failed to disable raw mode");
-        execute!(io::stdout(), LeaveAlternateScreen).expect("Failed to leave alternate screen");
+        execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen)
+            .expect("Failed to leave alternate screen");
         default_panic_handler(p);
     }));
     let _defer = DeferRestoreTerminal {};
-    let f = fs::File::open(&json_path)?;
-    let r = io::BufReader::new(f);
     let stdout = io::stdout();
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     let initial_layout = JexLayout::new(terminal.get_frame().size(), false);
-    let mut app = App::new(r, json_path, initial_layout)?;
+    let total_bytes = json_path
+        .filter(|&path| path != "-")
+        .and_then(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len());
+    let mut app = App::new_loading(r, name, format, total_bytes, initial_layout);
+    // The session's queries rerun on each batch as it loads
+    if let Some(session) = &session {
+        session.restore_tree(&mut app, initial_layout);
+    }
     terminal.draw(app.render(AppRenderMode::Normal))?;
     let mut query_rl: rustyline::Editor<()> = rustyline::Editor::new();
     let mut search_rl: rustyline::Editor<()> = rustyline::Editor::new();
+    let mut goto_rl: rustyline::Editor<()> = rustyline::Editor::new();
     let mut title_rl: rustyline::Editor<()> = rustyline::Editor::new();
+    let mut edit_rl: rustyline::Editor<()> = rustyline::Editor::new();
+    // The last layout json was saved with
+    let mut json_layout = JsonLayout::default();
     query_rl.bind_sequence(rustyline::KeyPress::Esc, rustyline::Cmd::Interrupt);
     search_rl.bind_sequence(rustyline::KeyPress::Esc, rustyline::Cmd::Interrupt);
     title_rl.bind_sequence(rustyline::KeyPress::Esc, rustyline::Cmd::Interrupt);
+    edit_rl.bind_sequence(rustyline::KeyPress::Esc, rustyline::Cmd::Interrupt);
     loop {
+        if app.loader.is_some() {
+            let layout = JexLayout::new(terminal.get_frame().size(), app.show_tree);
+            let changed = app.poll_loader(layout);
+            if app.loader.is_none() {
+                if let Some(session) = session.take() {
+                    session.restore_state(&mut app);
+                }
+                if let Some((values, name)) = diff_values.take() {
+                    app.compare_with(values, name, layout.right);
+                }
+            }
+            if changed {
+                terminal.draw(app.render(AppRenderMode::Normal))?;
+            }
+            if !event::poll(LOADER_POLL_INTERVAL).expect("Error polling for events") {
+                terminal.draw(app.render(AppRenderMode::Normal))?;
+                continue;
+            }
+        }
         let event = event::read().expect("Error getting next event");
         debug!("Event: {:?}", event);
         let c = match event {
             event::Event::Key(c) => c,
-            event::Event::Mouse(_) => panic!("Mouse events aren't enabled!"),
+            event::Event::Mouse(mouse) => {
+                if app.flash.is_some() {
+                    continue;
+                }
+                let layout = JexLayout::new(terminal.get_frame().size(), app.show_tree);
+                match mouse {
+                    MouseEvent::Down(MouseButton::Left, column, row, _) => {
+                        app.click(column, row, layout);
+                    }
+                    MouseEvent::ScrollDown(column, row, _) => {
+                        app.scroll(column, row, true, WHEEL_LINES, layout);
+                    }
+                    MouseEvent::ScrollUp(column, row, _) => {
+                        app.scroll(column, row, false, WHEEL_LINES, layout);
+                    }
+                    _ => continue,
+                }
+                terminal.draw(app.render(AppRenderMode::Normal))?;
+                continue;
+            }
             event::Event::Resize(width, height) => {
                 let rect = Rect {
                     x: 0,
@@ -245,15 +536,37 @@ fn run(json_path: String) -> Result<(), io::Error> {
             }
             KeyCode::Char('q') => {
                 terminal.draw(app.render(AppRenderMode::InputEditor))?;
-                let (_, _, query) = app.current_views_mut();
-                match query_rl.readline_with_initial("", (&*query, "")) {
+                let (_, right, query) = app.current_views_mut();
+                let original_query = query.clone();
+                let original_view = right.view.clone();
+                // Rerun the query as it's edited, so the right pane shows a preview of the results
+                let mut preview = |new_query: &str| {
+                    let (_, _, query) = app.current_views_mut();
+                    *query = new_query.to_owned();
+                    app.recompute_right(layout.right);
+                    terminal.draw(app.render(AppRenderMode::InputEditor))?;
+                    Ok(())
+                };
+                let mouse_capture = PauseMouseCapture::new();
+                let result = query_rl.readline_with_listener(
+                    "",
+                    (&original_query, ""),
+                    QUERY_PREVIEW_DELAY_MS,
+                    &mut preview,
+                );
+                drop(mouse_capture);
+                let (_, right, query) = app.current_views_mut();
+                match result {
                     Ok(new_query) => {
                         *query = new_query;
                         // Just in case rustyline messed stuff up
                         force_draw(&mut terminal, app.render(AppRenderMode::Normal))?;
                         app.recompute_right(layout.right);
                     }
-                    Err(_) => {}
+                    Err(_) => {
+                        *query = original_query;
+                        right.view = original_view;
+                    }
                 }
             }
             KeyCode::Tab => {
@@ -280,6 +593,7 @@ fn run(json_path: String) -> Result<(), io::Error> {
             KeyCode::Char('r') => {
                 terminal.draw(app.render(AppRenderMode::InputEditor))?;
                 let view_frame = app.focused_view_mut();
+                let _mouse_capture = PauseMouseCapture::new();
                 match title_rl.readline_with_initial("New Title:", (&view_frame.name, "")) {
                     Ok(new_name) => {
                         view_frame.name = new_name;
@@ -288,14 +602,59 @@ fn run(json_path: String) -> Result<(), io::Error> {
                 }
                 force_draw(&mut terminal, app.render(AppRenderMode::Normal))?;
             }
+            KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Char('p') => {
+                let kind = match c.code {
+                    KeyCode::Char('y') => CopyKind::Json,
+                    KeyCode::Char('Y') => CopyKind::Raw,
+                    _ => CopyKind::Path,
+                };
+                let result = match &app.focused_view().view {
+                    View::Json(Some(view)) => clipboard.copy(&copy_text(&view.cursor, kind)),
+                    View::Json(None) | View::Error(_) => Err("Nothing to copy".to_string()),
+                };
+                if let Err(err) = result {
+                    app.set_flash(err);
+                }
+            }
             KeyCode::Char('s') => {
                 terminal.draw(app.render(AppRenderMode::InputEditor))?;
                 let view_frame = app.focused_view();
                 if let View::Json(Some(view)) = &view_frame.view {
+                    let _mouse_capture = PauseMouseCapture::new();
                     match title_rl.readline_with_initial("Save to:", (&view_frame.name, "")) {
+                        // Compressed files can be read, but we only write plain ones
+                        Ok(path) if is_compressed_path(&path) => {
+                            app.set_flash("Can't save compressed files".to_string())
+                        }
                         Ok(path) => {
-                            if let Err(err) = view.save_to(&path) {
-                                app.set_flash(format!("Error saving json:\n{:?}", err));
+                            // Without a recognizable extension, save in the format we loaded
+                            let format = Format::from_path(&path).unwrap_or(format);
+                            let overwrite = !Path::new(&path).exists() || {
+                                force_draw(&mut terminal, app.render(AppRenderMode::InputEditor))?;
+                                let question = format!("{} already exists. Overwrite it?", path);
+                                confirm(&mut title_rl, &question)
+                            };
+                            let layout = if !overwrite {
+                                None
+                            } else if format.is_json() {
+                                force_draw(&mut terminal, app.render(AppRenderMode::InputEditor))?;
+                                let initial = json_layout.to_string();
+                                title_rl
+                                    .readline_with_initial("Layout:", (&initial, ""))
+                                    .ok()
+                                    .map(|layout| layout.parse::<JsonLayout>())
+                            } else {
+                                Some(Ok(json_layout))
+                            };
+                            match layout {
+                                Some(Ok(layout)) => {
+                                    json_layout = layout;
+                                    if let Err(err) = view.save_to(&path, format, layout, backup) {
+                                        app.set_flash(format!("Error saving json:\n{}", err));
+                                    }
+                                }
+                                Some(Err(err)) => app.set_flash(err),
+                                None => {}
                             }
                         }
                         Err(_) => {}
@@ -303,6 +662,216 @@ fn run(json_path: String) -> Result<(), io::Error> {
                 }
                 force_draw(&mut terminal, app.render(AppRenderMode::Normal))?;
             }
+            // Writes the root json, with any edits, back over the file it was loaded from
+            KeyCode::Char('S') => {
+                let result = match (&source, &app.views.view_frame.view) {
+                    (None, _) => Err(
+                        "The json came from stdin, so there's no file to write it back to"
+                            .to_string(),
+                    ),
+                    _ if app.loader.is_some() => {
+                        Err("The json is still loading, so it can't be written back yet"
+                            .to_string())
+                    }
+                    // Going by what the loader found, since the name can say otherwise
+                    _ if app.compressed => Err(
+                        "The file is compressed, and only plain files can be written back"
+                            .to_string(),
+                    ),
+                    _ if app.incomplete => Err(
+                        "Only some of the json loaded, so writing it back would lose the rest"
+                            .to_string(),
+                    ),
+                    // Edits anywhere else would be silently dropped
+                    _ if !app.index.parent.is_empty() || app.focus != Focus::Left => Err(
+                        "Only the left-most json is written back, so focus it before pressing S"
+                            .to_string(),
+                    ),
+                    (Some(source), View::Json(Some(view))) => {
+                        force_draw(&mut terminal, app.render(AppRenderMode::InputEditor))?;
+                        let _mouse_capture = PauseMouseCapture::new();
+                        let question = match format.write_back_loss() {
+                            Some(loss) => format!("Write back to {}? {}", source, loss),
+                            None => format!("Write back to {}?", source),
+                        };
+                        if confirm(&mut title_rl, &question) {
+                            // Keep json laid out the way the file had it. Only json has a
+                            // layout, so other formats can fail to detect one.
+                            let layout = fs::File::open(source)
+                                .and_then(|f| JsonLayout::detect(io::BufReader::new(f)))
+                                .unwrap_or_default();
+                            view.save_to(source, format, layout, backup)
+                                .map_err(|err| format!("Error saving json:\n{}", err))
+                        } else {
+                            Ok(())
+                        }
+                    }
+                    (Some(_), _) => Err("There's no json to write back".to_string()),
+                };
+                if let Err(err) = result {
+                    app.set_flash(err);
+                }
+                force_draw(&mut terminal, app.render(AppRenderMode::Normal))?;
+            }
+            // Writes the queries leading to the right pane out as a jq program or a shell script
+            KeyCode::Char('X') => {
+                let queries = app.views.queries(&app.index);
+                if let Some(queries) = queries {
+                    terminal.draw(app.render(AppRenderMode::InputEditor))?;
+                    let _mouse_capture = PauseMouseCapture::new();
+                    if let Ok(path) =
+                        title_rl.readline_with_initial("Export to:", ("export.sh", ""))
+                    {
+                        let exists = Path::new(&path).exists();
+                        let overwrite = !exists || {
+                            force_draw(&mut terminal, app.render(AppRenderMode::InputEditor))?;
+                            let question = format!("{} already exists. Overwrite it?", path);
+                            confirm(&mut title_rl, &question)
+                        };
+                        let text = if path.ends_with(".jq") {
+                            export::jq_program(&queries) + "\n"
+                        } else {
+                            let input = source.as_ref().and(export_input.as_deref());
+                            export::shell_script(&queries, input, format)
+                        };
+                        let result = if overwrite {
+                            save::write_atomically(Path::new(&path), backup, |w| {
+                                w.write_all(text.as_bytes())
+                            })
+                        } else {
+                            Ok(())
+                        };
+                        match result {
+                            Err(err) => app.set_flash(format!("Error exporting queries:\n{}", err)),
+                            // New scripts can be run straight away
+                            Ok(()) if overwrite && !exists && !path.ends_with(".jq") => {
+                                set_executable(Path::new(&path))
+                            }
+                            Ok(()) => {}
+                        }
+                    }
+                }
+                force_draw(&mut terminal, app.render(AppRenderMode::Normal))?;
+            }
+            KeyCode::Char('e')
+            | KeyCode::Char('E')
+            | KeyCode::Char('i')
+            | KeyCode::Char('x')
+            | KeyCode::Delete
+                if app.focus == Focus::Right =>
+            {
+                app.set_flash("Only the left pane can be edited".to_string());
+            }
+            KeyCode::Char('e') => {
+                if let Some(cursor) = app.left_cursor() {
+                    let initial = serde_json::to_string(&cursor.focus)?;
+                    if let Some(value) =
+                        prompt(&mut terminal, &app, &mut edit_rl, "Value:", &initial)?
+                    {
+                        let result = parse_value(&value)
+                            .and_then(|value| app.edit_left(Edit::Replace(value), layout.right));
+                        if let Err(err) = result {
+                            app.set_flash(err);
+                        }
+                    }
+                }
+            }
+            KeyCode::Char('E') => {
+                let key = match app.left_cursor().and_then(|cursor| cursor.frames.last()) {
+                    Some(CursorFrame::Object { key, .. }) => Some(key.clone()),
+                    _ => None,
+                };
+                match key {
+                    Some(key) => {
+                        if let Some(new_key) =
+                            prompt(&mut terminal, &app, &mut edit_rl, "Key:", key.value())?
+                        {
+                            if let Err(err) = app.edit_left(Edit::Rename(new_key), layout.right) {
+                                app.set_flash(err);
+                            }
+                        }
+                    }
+                    None => app.set_flash("Only values in objects have keys".to_string()),
+                }
+            }
+            KeyCode::Char('i') => {
+                let in_object = matches!(
+                    app.left_cursor().and_then(|cursor| cursor.frames.last()),
+                    Some(CursorFrame::Object { .. })
+                );
+                // Values in objects need a key, so we ask for that first
+                let key = if in_object {
+                    match prompt(&mut terminal, &app, &mut edit_rl, "Key:", "")? {
+                        Some(key) => Some(key),
+                        None => continue,
+                    }
+                } else {
+                    None
+                };
+                if let Some(value) = prompt(&mut terminal, &app, &mut edit_rl, "Value:", "")? {
+                    let result = parse_value(&value).and_then(|value| {
+                        app.edit_left(Edit::InsertAfter(key, value), layout.right)
+                    });
+                    if let Err(err) = result {
+                        app.set_flash(err);
+                    }
+                }
+            }
+            KeyCode::Char('x') | KeyCode::Delete => {
+                if let Err(err) = app.edit_left(Edit::Delete, layout.right) {
+                    app.set_flash(err);
+                }
+            }
+            KeyCode::Char('d') => {
+                if let Err(err) = app.toggle_diff() {
+                    app.set_flash(err);
+                }
+            }
+            // Adds a rule to fold everything that matches in the focused view. An empty rule clears
+            // them.
+            KeyCode::Char('R') => {
+                if let View::Json(Some(_)) = &app.focused_view().view {
+                    terminal.draw(app.render(AppRenderMode::InputEditor))?;
+                    let mouse_capture = PauseMouseCapture::new();
+                    let result = title_rl.readline_with_initial("Fold rule:", ("", ""));
+                    drop(mouse_capture);
+                    if let Ok(rule) = result {
+                        let rule = Some(rule)
+                            .filter(|rule| !rule.trim().is_empty())
+                            .map(|rule| rule.parse::<FoldRule>())
+                            .transpose();
+                        let view_frame = app.focused_view_mut();
+                        match rule {
+                            Ok(Some(rule)) => view_frame.add_fold_rule(rule),
+                            Ok(None) => view_frame.fold_rules.clear(),
+                            Err(err) => app.set_flash(err),
+                        }
+                    }
+                    force_draw(&mut terminal, app.render(AppRenderMode::Normal))?;
+                }
+            }
+            KeyCode::Char(':') => {
+                let initial = match &app.focused_view().view {
+                    View::Json(Some(view)) => Some(view.cursor.jq_path()),
+                    View::Json(None) | View::Error(_) => None,
+                };
+                if let Some(initial) = initial {
+                    terminal.draw(app.render(AppRenderMode::InputEditor))?;
+                    let mouse_capture = PauseMouseCapture::new();
+                    let result = goto_rl.readline_with_initial("Go to:", (&initial, ""));
+                    drop(mouse_capture);
+                    if let Ok(path) = result {
+                        let result = match &mut app.focused_view_mut().view {
+                            View::Json(Some(view)) => view.go_to_path(&path),
+                            View::Json(None) | View::Error(_) => Ok(()),
+                        };
+                        if let Err(err) = result {
+                            app.set_flash(err);
+                        }
+                    }
+                    force_draw(&mut terminal, app.render(AppRenderMode::Normal))?;
+                }
+            }
             KeyCode::Char('h') | KeyCode::Char('?') | KeyCode::F(1) => {
                 app.show_help();
             }
@@ -335,16 +904,55 @@ fn run(json_path: String) -> Result<(), io::Error> {
                     KeyCode::Char('z') => {
                         view.toggle_fold();
                     }
+                    KeyCode::Char('Z') => {
+                        view.toggle_fold_recursive();
+                    }
+                    KeyCode::Char('F') => {
+                        view.fold_siblings();
+                    }
+                    KeyCode::Char('0') => {
+                        view.unfold_all();
+                    }
+                    KeyCode::Char(c @ '1'..='9') => {
+                        view.fold_to_depth(c as usize - '0' as usize);
+                    }
                     KeyCode::Char('/') => {
                         terminal.draw(app.render(AppRenderMode::InputEditor))?;
-                        match search_rl.readline_with_initial("Search:", ("", "")) {
-                            Ok(new_search) => {
-                                // Just in case rustyline messed stuff up
-                                force_draw(&mut terminal, app.render(AppRenderMode::Normal))?;
-                                app.search_re = Regex::new(new_search.as_ref()).ok();
-                                app.search(false);
+                        let mouse_capture = PauseMouseCapture::new();
+                        let result = search_rl.readline_with_initial("Search:", ("", ""));
+                        drop(mouse_capture);
+                        if let Ok(new_search) = result {
+                            // Just in case rustyline messed stuff up
+                            force_draw(&mut terminal, app.render(AppRenderMode::Normal))?;
+                            match new_search.parse::<Search>() {
+                                Ok(search) => app.set_search(search),
+                                Err(err) => app.set_flash(err),
                             }
-                            Err(_) => {}
+                        }
+                    }
+                    KeyCode::Left => {
+                        if let Some(cursor) = view.cursor.parent() {
+                            view.jump_to(cursor);
+                        }
+                    }
+                    KeyCode::Right => {
+                        if let Some(cursor) = view.cursor.first_child() {
+                            view.jump_to(cursor);
+                        }
+                    }
+                    KeyCode::Char('>') => {
+                        if let Some(cursor) = view.cursor.last_child() {
+                            view.jump_to(cursor);
+                        }
+                    }
+                    KeyCode::Char('}') => {
+                        if let Some(cursor) = view.cursor.next_sibling() {
+                            view.jump_to(cursor);
+                        }
+                    }
+                    KeyCode::Char('{') => {
+                        if let Some(cursor) = view.cursor.prev_sibling() {
+                            view.jump_to(cursor);
                         }
                     }
                     KeyCode::Char('n') => {
@@ -353,6 +961,12 @@ fn run(json_path: String) -> Result<(), io::Error> {
                     KeyCode::Char('N') => {
                         app.search(true);
                     }
+                    KeyCode::Char(']') => {
+                        view.jump_to_diff(false);
+                    }
+                    KeyCode::Char('[') => {
+                        view.jump_to_diff(true);
+                    }
                     KeyCode::Home => {
                         view.scroll =
                             GlobalCursor::new(view.values.clone(), view.rect.width, &view.folds)
@@ -374,18 +988,95 @@ fn run(json_path: String) -> Result<(), io::Error> {
         }
         terminal.draw(app.render(AppRenderMode::Normal))?;
     }
+    // If we quit before the json finished loading, the session's folds and cursors were never
+    // restored, and the saved one is left as it was
+    if let (Some(path), None) = (session_path, session) {
+        Session::new(source, &app).save(path)?;
+    }
     // Gracefully freeing the JV values can take a significant amount of time and doesn't actually
     // benefit anything: the OS will clean up after us when we exit.
     std::mem::forget(app);
     Ok(())
 }
 
+// Reads a line of input in place of the query. Returns None if the user backs out with Esc.
+fn prompt<B: tui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    app: &App,
+    rl: &mut rustyline::Editor<()>,
+    prompt: &str,
+    initial: &str,
+) -> Result<Option<String>, io::Error> {
+    terminal.draw(app.render(AppRenderMode::InputEditor))?;
+    let mouse_capture = PauseMouseCapture::new();
+    let result = rl.readline_with_initial(prompt, (initial, "")).ok();
+    drop(mouse_capture);
+    force_draw(terminal, app.render(AppRenderMode::Normal))?;
+    Ok(result)
+}
+
+fn parse_value(s: &str) -> Result<JV, String> {
+    serde_json::from_str(s).map_err(|err| format!("Invalid json:\n{}", err))
+}
+
+// Runs the query the same way the viewer would, and prints the results like jq does.
+fn batch(args: &Args, query: &str) -> Result<(), io::Error> {
+    let r: Box<dyn Read + Send> = match args.json_path.as_deref() {
+        None | Some("-") => Box::new(io::stdin()),
+        Some(path) => Box::new(fs::File::open(path)?),
+    };
+    let format = input_format(args, args.json_path.as_deref());
+    let mut content = Vec::new();
+    for value in format.values(io::BufReader::new(decompress(r)?)) {
+        match value {
+            Ok(value) => content.push(value),
+            Err(err) if is_bad_line(&err) => eprintln!("Skipped {}", err),
+            Err(err) => {
+                match describe(&err) {
+                    (message, Some(Location { line, column })) => eprintln!(
+                        "Error loading json at line {}, column {}: {}",
+                        line, column, message
+                    ),
+                    (message, None) => eprintln!("Error loading json: {}", message),
+                }
+                process::exit(2);
+            }
+        }
+    }
+    let mut prog = match JQ::compile(query) {
+        Ok(prog) => prog,
+        Err(errs) => {
+            for err in errs {
+                eprintln!("{}", err);
+            }
+            process::exit(3);
+        }
+    };
+    let results = match run_jq_query(&content, &mut prog) {
+        Ok(results) => results,
+        Err(err) => {
+            eprintln!("jq: error: {}", err);
+            process::exit(5);
+        }
+    };
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    for result in results {
+        match &result {
+            JV::String(s) if args.raw_output => write!(out, "{}", s.value())?,
+            _ if args.compact_output => serde_json::to_writer(&mut out, &result)?,
+            _ => serde_json::to_writer_pretty(&mut out, &result)?,
+        }
+        writeln!(out)?;
+    }
+    out.flush()
+}
+
 #[cfg(feature = "dev-tools")]
-fn bench(json_path: String) -> Result<(), io::Error> {
+fn bench(json_path: Option<&str>) -> Result<(), io::Error> {
     let mut profiler = PROFILER.lock().unwrap();
     profiler.start("profile").unwrap();
-    let f = fs::File::open(&json_path)?;
-    let r = io::BufReader::new(f);
+    let (r, name) = open_input(json_path)?;
     let initial_layout = JexLayout {
         left: Rect {
             x: 0,
@@ -399,15 +1090,21 @@ fn bench(json_path: String) -> Result<(), io::Error> {
             width: 100,
             height: 100,
         },
-        query: Rect {
+        status: Rect {
             x: 0,
             y: 100,
             width: 100,
             height: 1,
         },
+        query: Rect {
+            x: 0,
+            y: 101,
+            width: 100,
+            height: 1,
+        },
         tree: None,
     };
-    let mut app = App::new(r, json_path, initial_layout)?;
+    let mut app = App::new(r, name, initial_layout)?;
     std::mem::forget(app);
     profiler.stop().unwrap();
     Ok(())
@@ -0,0 +1,86 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+// Writes to a temporary file next to the target, and renames it over the target once everything's
+// been written. If we crash or the write fails, the old file is left alone.
+pub fn write_atomically<F>(path: &Path, backup: bool, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut io::BufWriter<File>) -> io::Result<()>,
+{
+    // Renaming over a symlink would replace the link, rather than the file it points to
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let tmp_path = tmp_path(&path)?;
+    let result = write_file(&tmp_path, write).and_then(|()| {
+        if let Ok(metadata) = fs::metadata(&path) {
+            fs::set_permissions(&tmp_path, metadata.permissions())?;
+            if backup {
+                fs::copy(&path, backup_path(&path))?;
+            }
+        }
+        fs::rename(&tmp_path, &path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+fn write_file<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut io::BufWriter<File>) -> io::Result<()>,
+{
+    let mut w = io::BufWriter::new(File::create(path)?);
+    write(&mut w)?;
+    w.flush()?;
+    w.get_ref().sync_all()
+}
+
+fn tmp_path(path: &Path) -> io::Result<PathBuf> {
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} isn't a file name", path.display()),
+        )
+    })?;
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(format!(".jex-{}.tmp", process::id()));
+    Ok(path.with_file_name(tmp_name))
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backup_path, write_atomically};
+    use pretty_assertions::assert_eq;
+    use std::{fs, io, io::Write};
+    #[test]
+    fn unit_write_atomically() {
+        let dir = std::env::temp_dir().join(format!("jex-save-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.json");
+        fs::write(&path, "old").unwrap();
+        write_atomically(&path, true, |w| w.write_all(b"new")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "old");
+        // A failed write leaves the file as it was, and cleans up after itself
+        let failed = write_atomically(&path, false, |w| {
+            w.write_all(b"half")?;
+            Err(io::Error::new(io::ErrorKind::WriteZero, "failed"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{
+    cursor::{LeafCursor, ValuePath},
+    jq::jv::JV,
+};
+use regex::{Regex, RegexBuilder};
+use std::{collections::HashSet, fmt, rc::Rc, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchTarget {
+    KeysAndValues,
+    Keys,
+    Values,
+}
+
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub re: Regex,
+    pub target: SearchTarget,
+}
+
+impl Search {
+    pub fn matches(&self, cursor: &LeafCursor) -> bool {
+        let key_matches =
+            || matches!(cursor.current_key(), Some(key) if self.re.is_match(key.value()));
+        let value_matches =
+            || matches!(cursor.leaf_to_string(), Some(leaf) if self.re.is_match(&leaf));
+        match self.target {
+            SearchTarget::KeysAndValues => key_matches() || value_matches(),
+            SearchTarget::Keys => key_matches(),
+            SearchTarget::Values => value_matches(),
+        }
+    }
+    // Finds every match in the values (folded or not)
+    pub fn find_all(&self, jsons: Rc<[JV]>) -> Matches {
+        let mut paths = Vec::new();
+        let mock_folds = HashSet::new();
+        if let Some(mut cursor) = LeafCursor::new(jsons.clone()) {
+            loop {
+                if self.matches(&cursor) {
+                    paths.push(cursor.to_path());
+                }
+                if cursor.advance(&mock_folds).is_none() {
+                    break;
+                }
+            }
+        }
+        Matches { jsons, paths }
+    }
+}
+
+// Every match of a search in some values, found once so that n and N don't have to walk all of
+// them again
+#[derive(Debug, Clone)]
+pub struct Matches {
+    pub jsons: Rc<[JV]>,
+    paths: Vec<ValuePath>,
+}
+
+impl Matches {
+    // Works out which match the cursor is on
+    pub fn count(&self, cursor: &LeafCursor) -> MatchCount {
+        let path = cursor.to_path();
+        MatchCount {
+            index: self.paths.iter().position(|match_path| *match_path == path),
+            total: self.paths.len(),
+            path,
+        }
+    }
+}
+
+// The search box takes an optional word of flags before the pattern, like `-kw id`:
+// * k: only match keys
+// * v: only match values
+// * i: ignore case (by default, case is only ignored if the pattern is all lower case)
+// * c: match case
+// * l: match the pattern literally, rather than as a regex
+// * w: only match whole words
+impl FromStr for Search {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (flags, pattern) = match s.split_once(' ') {
+            Some((flags, pattern))
+                if flags.len() > 1
+                    && flags.starts_with('-')
+                    && flags[1..].chars().all(|c| "kvicwl".contains(c)) =>
+            {
+                (&flags[1..], pattern)
+            }
+            _ => ("", s),
+        };
+        let target = match (flags.contains('k'), flags.contains('v')) {
+            (true, false) => SearchTarget::Keys,
+            (false, true) => SearchTarget::Values,
+            _ => SearchTarget::KeysAndValues,
+        };
+        let ignore_case =
+            flags.contains('i') || !flags.contains('c') && !pattern.chars().any(char::is_uppercase);
+        let mut re = if flags.contains('l') {
+            regex::escape(pattern)
+        } else {
+            pattern.to_string()
+        };
+        if flags.contains('w') {
+            re = format!(r"\b(?:{})\b", re);
+        }
+        let re = RegexBuilder::new(&re)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|err| format!("Invalid search:\n{}", err))?;
+        Ok(Search { re, target })
+    }
+}
+
+// Where the cursor was among a search's matches, for the status bar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchCount {
+    pub path: ValuePath,
+    // None if the cursor isn't on a match
+    pub index: Option<usize>,
+    pub total: usize,
+}
+
+impl fmt::Display for MatchCount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.index, self.total) {
+            (_, 0) => write!(f, "no matches"),
+            (Some(index), total) => write!(f, "match {} of {}", index + 1, total),
+            (None, 1) => write!(f, "1 match"),
+            (None, total) => write!(f, "{} matches", total),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Search;
+    use crate::{cursor::LeafCursor, jq::jv::JV};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::collections::HashSet;
+    fn matches(search: &str) -> Vec<String> {
+        let search: Search = search.parse().unwrap();
+        let json: JV = (&json!({"Id": "id-1", "items": [{"id": "ID 2"}, "grid"]})).into();
+        let mut cursor = LeafCursor::new(vec![json].into()).unwrap();
+        let mut paths = Vec::new();
+        loop {
+            if search.matches(&cursor) {
+                paths.push(cursor.jq_path());
+            }
+            if cursor.advance(&HashSet::new()).is_none() {
+                return paths;
+            }
+        }
+    }
+    #[test]
+    fn unit_search_flags() {
+        assert_eq!(matches("id"), vec![".Id", ".items[0].id", ".items[1]"]);
+        assert_eq!(matches("Id"), vec![".Id"]);
+        assert_eq!(matches("-i Id"), matches("id"));
+        assert_eq!(matches("-kc id"), vec![".items[0].id"]);
+        assert_eq!(matches("-k id"), vec![".Id", ".items[0].id"]);
+        assert_eq!(matches("-v id"), vec![".Id", ".items[0].id", ".items[1]"]);
+        assert_eq!(matches("-vw id"), vec![".Id", ".items[0].id"]);
+        assert_eq!(matches("-kw id"), vec![".Id", ".items[0].id"]);
+        assert_eq!(matches("-l i.-"), Vec::<String>::new());
+        assert_eq!(matches("i.-"), vec![".Id"]);
+        // Anything that isn't a word of flags is part of the pattern
+        assert_eq!(matches("-x id"), Vec::<String>::new());
+        assert!("(".parse::<Search>().is_err());
+    }
+    #[test]
+    fn unit_search_count() {
+        let search: Search = "-v id".parse().unwrap();
+        let json: JV = (&json!({"a": "id", "b": "x", "c": ["id", "id"]})).into();
+        let mut cursor = LeafCursor::new(vec![json].into()).unwrap();
+        let matches = search.find_all(cursor.jsons.clone());
+        assert_eq!(matches.count(&cursor).to_string(), "3 matches");
+        cursor = cursor.search_by(|cursor| search.matches(cursor)).unwrap();
+        cursor = cursor.search_by(|cursor| search.matches(cursor)).unwrap();
+        assert_eq!(matches.count(&cursor).to_string(), "match 2 of 3");
+        let none: Search = "nope".parse().unwrap();
+        let no_matches = none.find_all(cursor.jsons.clone());
+        assert_eq!(no_matches.count(&cursor).to_string(), "no matches");
+    }
+}
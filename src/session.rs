@@ -0,0 +1,247 @@
+use crate::{
+    app::App,
+    cursor::ValuePath,
+    fold::FoldRule,
+    layout::JexLayout,
+    save::write_atomically,
+    view_tree::{JsonView, View, ViewFrame, ViewTree, ViewTreeIndex},
+};
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+// A saved exploration. We don't store any json: the queries are rerun against the source file when
+// the session is restored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    // None if the json was read from stdin
+    pub source: Option<String>,
+    pub index: ViewTreeIndex,
+    pub root: SessionTree,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionTree {
+    pub name: String,
+    pub folds: Vec<(usize, Vec<usize>)>,
+    pub cursor: Option<ValuePath>,
+    // Sessions saved before fold rules existed don't have any
+    #[serde(default)]
+    pub fold_rules: Vec<FoldRule>,
+    pub children: Vec<(String, SessionTree)>,
+}
+
+impl Session {
+    pub fn new(source: Option<String>, app: &App) -> Self {
+        Session {
+            source,
+            index: app.index.clone(),
+            root: SessionTree::new(&app.views),
+        }
+    }
+    pub fn load(path: &str) -> io::Result<Self> {
+        let f = fs::File::open(path)?;
+        let session = serde_json::from_reader(io::BufReader::new(f))?;
+        Ok(session)
+    }
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        write_atomically(Path::new(path), false, |w| {
+            serde_json::to_writer_pretty(w, self)?;
+            Ok(())
+        })
+    }
+    // Rebuilds the tree of views on top of the app's root view, which should already be loaded from
+    // the source.
+    pub fn restore(&self, app: &mut App, layout: JexLayout) {
+        self.restore_tree(app, layout);
+        self.restore_state(app);
+    }
+    // Rebuilds the queries, names and fold rules. This can happen before the json is loaded, since
+    // the queries rerun on each batch as it comes in.
+    pub fn restore_tree(&self, app: &mut App, layout: JexLayout) {
+        self.root.restore_tree(&mut app.views, layout);
+        if app.views.index(&self.index).is_some() {
+            app.index = self.index.clone();
+        }
+    }
+    // Folds and cursors need all of the json, so this waits until it's loaded. Views opened in
+    // the meantime are kept.
+    pub fn restore_state(&self, app: &mut App) {
+        self.root.restore_state(&mut app.views);
+    }
+}
+
+impl SessionTree {
+    fn new(tree: &ViewTree) -> Self {
+        let (folds, cursor) = match &tree.view_frame.view {
+            View::Json(Some(view)) => {
+                let mut folds: Vec<_> = view.folds.iter().cloned().collect();
+                folds.sort();
+                (folds, Some(view.cursor.to_path()))
+            }
+            View::Json(None) | View::Error(_) => (Vec::new(), None),
+        };
+        let children = tree
+            .children
+            .iter()
+            .map(|(query, child)| (query.clone(), SessionTree::new(child)))
+            .collect();
+        SessionTree {
+            name: tree.view_frame.name.clone(),
+            folds,
+            cursor,
+            fold_rules: tree.view_frame.fold_rules.clone(),
+            children,
+        }
+    }
+    fn restore_tree(&self, tree: &mut ViewTree, layout: JexLayout) {
+        tree.view_frame.name = self.name.clone();
+        tree.view_frame.fold_rules = self.fold_rules.clone();
+        tree.view_frame.apply_fold_rules(0);
+        let parent_view = &tree.view_frame.view;
+        tree.children = self
+            .children
+            .iter()
+            .map(|(query, child)| {
+                let view = match parent_view {
+                    View::Json(Some(parent)) => parent.apply_query(query, layout.right),
+                    View::Json(None) | View::Error(_) => View::Json(None),
+                };
+                let view_frame = ViewFrame {
+                    view,
+                    name: child.name.clone(),
+                    fold_rules: Vec::new(),
+                };
+                let mut child_tree = ViewTree {
+                    view_frame,
+                    children: Vec::new(),
+                };
+                child.restore_tree(&mut child_tree, layout);
+                (query.clone(), child_tree)
+            })
+            .collect();
+    }
+    // Children are matched up by position, and skipped if their query has been changed since
+    fn restore_state(&self, tree: &mut ViewTree) {
+        if let View::Json(Some(view)) = &mut tree.view_frame.view {
+            self.restore_view(view);
+        }
+        for ((query, child), (tree_query, child_tree)) in
+            self.children.iter().zip(tree.children.iter_mut())
+        {
+            if query == tree_query {
+                child.restore_state(child_tree);
+            }
+        }
+    }
+    // The source might have changed since the session was saved, so we drop any folds or cursor
+    // that no longer fit the json.
+    fn restore_view(&self, view: &mut JsonView) {
+        view.restore_state(self.folds.iter().cloned(), self.cursor.as_ref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Session;
+    use crate::{app::App, format::Format, layout::JexLayout, view_tree::View};
+    use pretty_assertions::assert_eq;
+    use std::collections::HashSet;
+    use tui::layout::Rect;
+    const JSON: &str =
+        r#"{"items": [{"name": "a", "tags": [1, 2]}, {"name": "b"}]} {"items": [{"name": "c"}]}"#;
+    fn new_app(layout: JexLayout) -> App {
+        App::new(JSON.as_bytes(), "test".to_string(), layout).unwrap()
+    }
+    #[test]
+    fn unit_session_roundtrip() {
+        let layout = JexLayout::new(Rect::new(0, 0, 100, 40), false);
+        let mut app = new_app(layout);
+        {
+            let (_, _, query) = app.current_views_mut();
+            *query = ".items[]".to_string();
+        }
+        app.recompute_right(layout.right);
+        {
+            let (left, right, _) = app.current_views_mut();
+            left.name = "root".to_string();
+            right.name = "items".to_string();
+            right.add_fold_rule("key tags".parse().unwrap());
+            if let View::Json(Some(view)) = &mut right.view {
+                view.toggle_fold();
+                view.advance_cursor();
+                view.toggle_fold();
+            } else {
+                panic!("Query should have succeeded");
+            }
+        }
+        let session = Session::new(Some("test.json".to_string()), &app);
+        let serialized = serde_json::to_string(&session).unwrap();
+        let deserialized: Session = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(session, deserialized);
+        let mut restored = new_app(layout);
+        deserialized.restore(&mut restored, layout);
+        assert_eq!(
+            Session::new(Some("test.json".to_string()), &restored),
+            session
+        );
+        let (left, right, query) = restored.current_views_mut();
+        assert_eq!(left.name, "root");
+        assert_eq!(right.name, "items");
+        assert_eq!(query, ".items[]");
+        if let View::Json(Some(view)) = &right.view {
+            let expected: HashSet<_> = vec![(0, vec![]), (0, vec![1]), (1, vec![])]
+                .into_iter()
+                .collect();
+            assert_eq!(view.folds, expected);
+        } else {
+            panic!("Query should have been rerun");
+        }
+    }
+    #[test]
+    fn unit_session_restore_while_loading() {
+        let layout = JexLayout::new(Rect::new(0, 0, 100, 40), false);
+        let mut app = new_app(layout);
+        {
+            let (_, right, query) = app.current_views_mut();
+            *query = ".items[]".to_string();
+            right.name = "items".to_string();
+        }
+        app.recompute_right(layout.right);
+        if let View::Json(Some(view)) = &mut app.current_views_mut().1.view {
+            view.toggle_fold();
+            view.advance_cursor();
+        }
+        let session = Session::new(None, &app);
+        let format = Format::Json;
+        let mut loading =
+            App::new_loading(JSON.as_bytes(), "test".to_string(), format, None, layout);
+        session.restore_tree(&mut loading, layout);
+        // Opened before the json finished loading
+        loading.views.push_trivial_child(layout.right);
+        while loading.loader.is_some() {
+            loading.poll_loader(layout);
+            std::thread::yield_now();
+        }
+        session.restore_state(&mut loading);
+        assert_eq!(loading.views.children.len(), 2);
+        assert_eq!(
+            Session::new(None, &loading).root.children[0],
+            session.root.children[0]
+        );
+    }
+    #[test]
+    fn unit_session_stale_paths() {
+        let layout = JexLayout::new(Rect::new(0, 0, 100, 40), false);
+        let mut app = new_app(layout);
+        let mut session = Session::new(None, &app);
+        session.root.folds = vec![(5, vec![]), (0, vec![0, 0, 0, 0]), (1, vec![])];
+        session.root.cursor = None;
+        session.restore(&mut app, layout);
+        if let View::Json(Some(view)) = &app.focused_view().view {
+            let expected: HashSet<_> = vec![(1, vec![])].into_iter().collect();
+            assert_eq!(view.folds, expected);
+        } else {
+            panic!("Root view should be json");
+        }
+    }
+}
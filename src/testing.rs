@@ -1,5 +1,5 @@
 use crate::{
-    jq::jv::JVString,
+    jq::jv::{JVNumber, JVString},
     lines::{Leaf, LeafContent},
 };
 use proptest::prelude::*;
@@ -67,7 +67,7 @@ fn json_to_lines_inner(
         Value::Number(x) => {
             push_line(
                 key,
-                LeafContent::Number(x.as_f64().unwrap()),
+                LeafContent::Number(JVNumber::from_literal(&x.to_string()).unwrap()),
                 indent,
                 out,
                 comma,
@@ -1,15 +1,27 @@
 use crate::{
     cursor::{FocusPosition, GlobalCursor, GlobalPath, LeafCursor, ValuePath},
+    diff::DiffMarks,
+    edit::{apply_edit, Edit},
+    fold::FoldRule,
+    format::{Format, JsonLayout},
     jq::{
         jv::JV,
         query::{run_jq_query, JQ},
     },
     layout::JexLayout,
     lines::LineCursor,
+    loader::Loader,
+    save::write_atomically,
 };
 use log::trace;
-use serde_json::Deserializer;
-use std::{collections::HashSet, io, io::Write, ops::RangeInclusive, rc::Rc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    io,
+    ops::RangeInclusive,
+    path::Path,
+    rc::Rc,
+};
 use tui::{
     layout::{Alignment, Rect},
     style::{Color, Style},
@@ -27,36 +39,120 @@ pub struct ViewTree {
 pub struct ViewFrame {
     pub view: View,
     pub name: String,
+    // Reapplied whenever the view's values change, like when its query is rerun
+    pub fold_rules: Vec<FoldRule>,
+}
+
+impl ViewFrame {
+    pub fn add_fold_rule(&mut self, rule: FoldRule) {
+        if let View::Json(Some(view)) = &mut self.view {
+            view.apply_fold_rules(std::slice::from_ref(&rule), 0);
+        }
+        self.fold_rules.push(rule);
+    }
+    // Folds everything the rules match in the values from start on
+    pub fn apply_fold_rules(&mut self, start: usize) {
+        if let View::Json(Some(view)) = &mut self.view {
+            view.apply_fold_rules(&self.fold_rules, start);
+        }
+    }
 }
 
 impl ViewTree {
-    pub fn new_from_reader<R: io::Read>(r: R, name: String, layout: JexLayout) -> io::Result<Self> {
-        let content: Vec<JV> = Deserializer::from_reader(r)
-            .into_iter::<JV>()
-            .collect::<Result<Vec<JV>, _>>()?;
-        let view = View::new(content, layout.left);
-        let view_frame = ViewFrame { view, name };
+    pub fn new(values: Vec<JV>, name: String, layout: JexLayout) -> Self {
+        let view = View::new(values, layout.left);
+        let view_frame = ViewFrame {
+            view,
+            name,
+            fold_rules: Vec::new(),
+        };
         let mut tree = ViewTree {
             view_frame,
             children: Vec::new(),
         };
         tree.push_trivial_child(layout.right);
-        Ok(tree)
+        tree
     }
-    pub fn push_trivial_child(&mut self, target_view_rect: Rect) {
-        if let View::Json(Some(view)) = &self.view_frame.view {
-            let name = "New Query".into();
-            let view_frame = ViewFrame {
-                view: View::new(view.values.clone(), target_view_rect),
-                name,
-            };
-            let child = ViewTree {
-                view_frame,
-                children: Vec::new(),
+    // Starts out empty: the loader's values get added with append_loaded as they're parsed.
+    pub fn new_from_reader<R: io::Read + Send + 'static>(
+        r: R,
+        name: String,
+        format: Format,
+        total_bytes: Option<u64>,
+        layout: JexLayout,
+    ) -> (Self, Loader) {
+        let tree = ViewTree::new(Vec::new(), name, layout);
+        (tree, Loader::spawn(r, format, total_bytes))
+    }
+    // Programs holds the compiled queries, so that they're compiled once per load rather than for
+    // every batch.
+    pub fn append_loaded(
+        &mut self,
+        values: Vec<JV>,
+        layout: JexLayout,
+        programs: &mut HashMap<String, JQ>,
+    ) {
+        self.append_values(values, layout.left, layout.right, programs);
+    }
+    // jq runs a query on each value separately, so the children only need to run their queries on
+    // the new values, rather than on everything loaded so far.
+    fn append_values(
+        &mut self,
+        values: Vec<JV>,
+        view_rect: Rect,
+        target_view_rect: Rect,
+        programs: &mut HashMap<String, JQ>,
+    ) {
+        let start = match &mut self.view_frame.view {
+            View::Json(Some(view)) => {
+                let start = view.values.len();
+                view.append_values(values.clone());
+                start
+            }
+            view => {
+                *view = View::new(values.clone(), view_rect);
+                0
+            }
+        };
+        self.view_frame.apply_fold_rules(start);
+        for (query, child) in self.children.iter_mut() {
+            // The query already failed on an earlier value
+            if let View::Error(_) = child.view_frame.view {
+                continue;
+            }
+            let prog = match programs.entry(query.clone()) {
+                Entry::Occupied(entry) => Ok(entry.into_mut()),
+                Entry::Vacant(entry) => JQ::compile(query).map(|prog| entry.insert(prog)),
             };
-            self.children.push((".".to_string(), child));
+            let results =
+                prog.and_then(|prog| run_jq_query(values.iter(), prog).map_err(|e| vec![e]));
+            match results {
+                Ok(results) => {
+                    child.append_values(results, target_view_rect, target_view_rect, programs)
+                }
+                Err(err) => {
+                    child.view_frame.view = View::Error(err);
+                    child.rerun_descendants(target_view_rect);
+                }
+            }
         }
     }
+    pub fn push_trivial_child(&mut self, target_view_rect: Rect) {
+        let view = match &self.view_frame.view {
+            View::Json(Some(view)) => View::new(view.values.clone(), target_view_rect),
+            View::Json(None) | View::Error(_) => View::Json(None),
+        };
+        let view_frame = ViewFrame {
+            view,
+            name: "New Query".into(),
+            fold_rules: Vec::new(),
+        };
+        let child = ViewTree {
+            view_frame,
+            children: Vec::new(),
+        };
+        self.children.push((".".to_string(), child));
+    }
     pub fn index_tree(&self, mut path: &[usize]) -> Option<&Self> {
         let mut focus = self;
         while let Some((&i, new_path)) = path.split_first() {
@@ -86,6 +182,57 @@ impl ViewTree {
         let (query, child_tree) = focus.children.get_mut(ix.child)?;
         Some((&mut focus.view_frame, &mut child_tree.view_frame, query))
     }
+    // Reruns the queries below this tree, for when its json has changed. Folds and cursors are kept
+    // wherever they still fit the new results.
+    pub fn rerun_descendants(&mut self, target_view_rect: Rect) {
+        let ViewTree {
+            view_frame,
+            children,
+        } = self;
+        for (query, child) in children.iter_mut() {
+            let mut view = match &view_frame.view {
+                View::Json(Some(parent)) => parent.apply_query(query, target_view_rect),
+                View::Json(None) | View::Error(_) => View::Json(None),
+            };
+            if let (View::Json(Some(new)), View::Json(Some(old))) =
+                (&mut view, &child.view_frame.view)
+            {
+                new.restore_state(old.folds.iter().cloned(), Some(&old.cursor.to_path()));
+            }
+            child.view_frame.view = view;
+            child.view_frame.apply_fold_rules(0);
+            child.rerun_descendants(target_view_rect);
+        }
+    }
+    // The queries from the root down to the index's right pane, in the order they're run
+    pub fn queries(&self, ix: &ViewTreeIndex) -> Option<Vec<&str>> {
+        let mut queries = Vec::new();
+        let mut focus = self;
+        for &i in ix.parent.iter() {
+            let (query, child) = focus.children.get(i)?;
+            queries.push(query.as_str());
+            focus = child;
+        }
+        queries.push(focus.children.get(ix.child)?.0.as_str());
+        Some(queries)
+    }
+    // The index that makes the view on this row of render_tree the right pane. The root is on the
+    // first row, and can't be a right pane.
+    pub fn index_at_row(&self, row: usize) -> Option<ViewTreeIndex> {
+        let mut paths = Vec::new();
+        self.collect_paths(&mut Vec::new(), &mut paths);
+        let mut parent = paths.into_iter().nth(row.checked_sub(1)?)?;
+        let child = parent.pop()?;
+        Some(ViewTreeIndex { parent, child })
+    }
+    fn collect_paths(&self, prefix: &mut Vec<usize>, out: &mut Vec<Vec<usize>>) {
+        for (i, (_, child)) in self.children.iter().enumerate() {
+            prefix.push(i);
+            out.push(prefix.clone());
+            child.collect_paths(prefix, out);
+            prefix.pop();
+        }
+    }
     pub fn render_tree(&self, index: &ViewTreeIndex) -> Paragraph {
         let is_parent = index.parent.is_empty();
         let mut spans = vec![render_tree_entry(&self.view_frame.name, is_parent, false).into()];
@@ -99,6 +246,21 @@ impl ViewTree {
     }
 }
 
+// Calls f with the path of every array and object in json, starting with json itself (at path)
+fn for_each_container<F: FnMut(&[usize])>(json: &JV, path: &mut Vec<usize>, f: &mut F) {
+    let children: Vec<JV> = match json {
+        JV::Array(arr) => arr.iter().collect(),
+        JV::Object(obj) => obj.values().collect(),
+        _ => return,
+    };
+    f(path);
+    for (i, child) in children.iter().enumerate() {
+        path.push(i);
+        for_each_container(child, path, f);
+        path.pop();
+    }
+}
+
 fn render_tree_inner<'a, 'b>(
     tree: &'a ViewTree,
     prefix: &str,
@@ -136,6 +298,7 @@ fn render_tree_entry(name: &str, is_parent: bool, is_child: bool) -> Span {
     Span::styled(name, style)
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ViewTreeIndex {
     pub parent: Vec<usize>,
     pub child: usize,
@@ -257,6 +420,17 @@ pub struct JsonView {
     pub cursor: LeafCursor,
     pub folds: HashSet<(usize, Vec<usize>)>,
     pub rect: Rect,
+    // Set while this view is being compared against another one
+    pub diff: Option<Rc<DiffMarks>>,
+    // Put back once the comparison stops
+    before_diff: Option<SavedState>,
+}
+
+// The folds and cursor a view had before something took them over
+#[derive(Debug, Clone)]
+struct SavedState {
+    folds: HashSet<(usize, Vec<usize>)>,
+    cursor: ValuePath,
 }
 
 impl JsonView {
@@ -271,13 +445,17 @@ impl JsonView {
             cursor,
             folds,
             rect,
+            diff: None,
+            before_diff: None,
         })
     }
     fn render(&self, rect: Rect, has_focus: bool) -> Paragraph {
         trace!("Rendering started: target rect {:?}", rect);
         let JsonView { cursor, scroll, .. } = self;
         let cursor = if has_focus { Some(cursor) } else { None };
-        let text = scroll.clone().render_lines(cursor, &self.folds, rect);
+        let text = scroll
+            .clone()
+            .render_lines(cursor, &self.folds, self.diff.as_deref(), rect);
         trace!("Rendering complete");
         Paragraph::new(text)
             .style(Style::default().fg(Color::White).bg(Color::Black))
@@ -337,11 +515,83 @@ impl JsonView {
             };
         }
     }
+    // Moves the cursor to the value drawn at this position (relative to the view's rect). Clicking
+    // the bracket of an array or object folds or unfolds it.
+    pub fn click(&mut self, column: u16, row: u16) {
+        let target = match self.scroll.at_row(row, &self.folds, self.rect.width) {
+            Some(target) => target,
+            None => return,
+        };
+        let on_bracket = target.value_cursor.focus_position != FocusPosition::Value
+            && target.current_line().is_value_at(column);
+        self.cursor = target.value_cursor;
+        if on_bracket {
+            self.toggle_fold();
+        }
+    }
+    // Scrolls the view without moving the cursor, unless it would end up offscreen.
+    pub fn scroll_down(&mut self, lines: usize) {
+        for _ in 0..lines {
+            let at_bottom = self
+                .scroll
+                .at_row(self.rect.height, &self.folds, self.rect.width)
+                .is_none();
+            if at_bottom || self.scroll.advance(&self.folds, self.rect.width).is_none() {
+                break;
+            }
+        }
+        if !self
+            .visible_range(&self.folds)
+            .contains_value(&self.cursor.to_path())
+        {
+            self.cursor = self.scroll.value_cursor.clone();
+        }
+    }
+    pub fn scroll_up(&mut self, lines: usize) {
+        for _ in 0..lines {
+            if self.scroll.regress(&self.folds, self.rect.width).is_none() {
+                break;
+            }
+        }
+        if !self
+            .visible_range(&self.folds)
+            .contains_value(&self.cursor.to_path())
+        {
+            let bottom = self.rect.height.saturating_sub(1);
+            if let Some(target) = self.scroll.at_row(bottom, &self.folds, self.rect.width) {
+                self.cursor = target.value_cursor;
+            }
+        }
+    }
     pub fn unfold_around_cursor(&mut self) {
         let mut path = self.cursor.to_path().strip_position();
-        while !path.1.is_empty() {
+        loop {
             self.folds.remove(&path);
-            path.1.pop();
+            if path.1.pop().is_none() {
+                break;
+            }
+        }
+    }
+    // Moves the cursor to a jq path (like `.items[3].name`) in the value it's currently in,
+    // opening any folds on the way.
+    pub fn go_to_path(&mut self, path: &str) -> Result<(), String> {
+        let path = ValuePath::from_jq_path(&self.values, self.cursor.top_index, path)?;
+        self.jump_to(LeafCursor::from_path(self.values.clone(), &path));
+        Ok(())
+    }
+    // Moves the cursor somewhere else in the values, opening the folds it's inside (but not its own
+    // fold) and scrolling to it if it's offscreen.
+    pub fn jump_to(&mut self, cursor: LeafCursor) {
+        self.cursor = cursor;
+        let mut path = self.cursor.to_path().strip_position();
+        while path.1.pop().is_some() {
+            self.folds.remove(&path);
+        }
+        if !self
+            .visible_range(&self.folds)
+            .contains_value(&self.cursor.to_path())
+        {
+            self.scroll_to_cursor();
         }
     }
     pub fn toggle_fold(&mut self) {
@@ -358,15 +608,190 @@ impl JsonView {
                 .value_cursor
                 .descends_from_or_matches(&self.cursor)
             {
-                let line = self.cursor.current_line(&self.folds, self.rect.width);
-                let line_cursor = LineCursor::new_at_start(line.render(), self.rect.width);
+                // Note: this is okay because you can only fold objects and arrays
+                self.scroll_to_cursor();
+            }
+        }
+    }
+    // Folds every array and object nested depth or more levels down, and unfolds the ones above
+    // them, so the view shows an outline of the values.
+    pub fn fold_to_depth(&mut self, depth: usize) {
+        let mut folds = HashSet::new();
+        for (top_index, value) in self.values.iter().enumerate() {
+            for_each_container(value, &mut Vec::new(), &mut |path| {
+                if path.len() >= depth {
+                    folds.insert((top_index, path.to_vec()));
+                }
+            });
+        }
+        self.folds = folds;
+        self.reveal_cursor();
+    }
+    pub fn unfold_all(&mut self) {
+        self.folds.clear();
+    }
+    // Folds everything beside the value under the cursor, leaving the value itself alone
+    pub fn fold_siblings(&mut self) {
+        let (top_index, mut path) = self.cursor.to_path().strip_position();
+        let own_index = path.pop();
+        let siblings: Vec<(usize, Vec<usize>)> = match own_index {
+            None => (0..self.values.len()).map(|i| (i, Vec::new())).collect(),
+            Some(_) => {
+                let parent = LeafCursor::try_from_stripped_path(
+                    self.values.clone(),
+                    (top_index, path.clone()),
+                )
+                .expect("The cursor's parent should exist");
+                let len = match &parent.focus {
+                    JV::Array(arr) => arr.len() as usize,
+                    JV::Object(obj) => obj.len() as usize,
+                    _ => 0,
+                };
+                (0..len)
+                    .map(|i| (top_index, path.iter().copied().chain(Some(i)).collect()))
+                    .collect()
+            }
+        };
+        let own = self.cursor.to_path().strip_position();
+        for sibling in siblings.into_iter().filter(|sibling| *sibling != own) {
+            let cursor = LeafCursor::try_from_stripped_path(self.values.clone(), sibling.clone());
+            if let Some(JV::Array(_)) | Some(JV::Object(_)) = cursor.map(|cursor| cursor.focus) {
+                self.folds.insert(sibling);
+            }
+        }
+        self.reveal_cursor();
+    }
+    // Unfolds the value under the cursor and everything inside it if it's folded, or folds them
+    // all if it isn't.
+    pub fn toggle_fold_recursive(&mut self) {
+        match self.cursor.focus {
+            JV::Array(_) | JV::Object(_) => {}
+            _ => return,
+        }
+        let (top_index, mut path) = self.cursor.to_path().strip_position();
+        if self.folds.contains(&(top_index, path.clone())) {
+            self.folds
+                .retain(|(fold_index, fold)| *fold_index != top_index || !fold.starts_with(&path));
+            return;
+        }
+        let folds = &mut self.folds;
+        for_each_container(&self.cursor.focus, &mut path, &mut |path| {
+            folds.insert((top_index, path.to_vec()));
+        });
+        self.reveal_cursor();
+    }
+    // Folds everything the rules match in the values from start on
+    pub fn apply_fold_rules(&mut self, rules: &[FoldRule], start: usize) {
+        for rule in rules {
+            self.folds.extend(rule.folds(&self.values, start));
+        }
+        self.reveal_cursor();
+    }
+    // After folding a lot at once, the cursor and the top line of the view might be hidden inside
+    // a fold. Moves them out to the outermost fold around them.
+    fn reveal_cursor(&mut self) {
+        if let Some(cursor) = self.outermost_fold(&self.cursor) {
+            self.cursor = cursor;
+        }
+        if let Some(scroll) = self.outermost_fold(&self.scroll.value_cursor) {
+            let line = scroll.current_line(&self.folds, self.rect.width);
+            self.scroll = GlobalCursor {
+                value_cursor: scroll,
+                line_cursor: LineCursor::new_at_start(line.render(), self.rect.width),
+            };
+        }
+        if !self
+            .visible_range(&self.folds)
+            .contains_value(&self.cursor.to_path())
+        {
+            self.scroll_to_cursor();
+        }
+    }
+    fn outermost_fold(&self, cursor: &LeafCursor) -> Option<LeafCursor> {
+        let (top_index, path) = cursor.to_path().strip_position();
+        let len = (0..=path.len()).find(|&len| {
+            let fold = (top_index, path[..len].to_vec());
+            // The cursor can sit on the opening bracket of its own fold, but not its closing one
+            self.folds.contains(&fold)
+                && (len < path.len() || cursor.focus_position == FocusPosition::End)
+        })?;
+        LeafCursor::try_from_stripped_path(self.values.clone(), (top_index, path[..len].to_vec()))
+    }
+    // Scrolls so that the first line of the cursor is at the top of the view.
+    pub fn scroll_to_cursor(&mut self) {
+        let line = self.cursor.current_line(&self.folds, self.rect.width);
+        let line_cursor = LineCursor::new_at_start(line.render(), self.rect.width);
+        self.scroll = GlobalCursor {
+            value_cursor: self.cursor.clone(),
+            line_cursor,
+        };
+    }
+    // Takes folds and a cursor from some other json (eg, an older version of this one), dropping
+    // any that no longer fit.
+    pub fn restore_state<I: IntoIterator<Item = (usize, Vec<usize>)>>(
+        &mut self,
+        folds: I,
+        cursor: Option<&ValuePath>,
+    ) {
+        let values = self.values.clone();
+        self.folds = folds
+            .into_iter()
+            .filter(|fold| {
+                let path = ValuePath::from_stripped(fold.clone());
+                LeafCursor::try_from_path(values.clone(), &path).is_some()
+            })
+            .collect();
+        let cursor = cursor.and_then(|path| LeafCursor::try_from_path(values, path));
+        if let Some(cursor) = cursor {
+            self.cursor = cursor;
+            self.scroll_to_cursor();
+        }
+    }
+    // For values that are still being loaded. Paths stay valid when values are added at the end.
+    pub fn append_values(&mut self, values: Vec<JV>) {
+        let mut all = self.values.to_vec();
+        all.extend(values);
+        self.values = all.into();
+        self.cursor = LeafCursor::from_path(self.values.clone(), &self.cursor.to_path());
+        self.scroll.value_cursor =
+            LeafCursor::from_path(self.values.clone(), &self.scroll.value_cursor.to_path());
+    }
+    pub fn apply_edit(&mut self, edit: Edit) -> Result<(), String> {
+        let edited = apply_edit(&self.cursor, &self.folds, edit)?;
+        let scroll_path = self.scroll.value_cursor.to_path();
+        self.values = edited.values;
+        self.folds = edited.folds;
+        self.cursor = LeafCursor::try_from_stripped_path(self.values.clone(), edited.cursor)
+            .expect("Edited cursor should point into the edited json");
+        // Stay where we were, unless that's gone or the cursor would be offscreen
+        let scroll_cursor = LeafCursor::try_from_path(self.values.clone(), &scroll_path);
+        match scroll_cursor {
+            Some(scroll_cursor) if !self.is_folded_away(&scroll_cursor) => {
+                let line = scroll_cursor.current_line(&self.folds, self.rect.width);
                 self.scroll = GlobalCursor {
-                    value_cursor: self.cursor.clone(),
-                    // Note: this is okay because you can only fold objects and arrays
-                    line_cursor,
+                    value_cursor: scroll_cursor,
+                    line_cursor: LineCursor::new_at_start(line.render(), self.rect.width),
                 };
+                if !self
+                    .visible_range(&self.folds)
+                    .contains_value(&self.cursor.to_path())
+                {
+                    self.scroll_to_cursor();
+                }
             }
+            _ => self.scroll_to_cursor(),
         }
+        Ok(())
+    }
+    // Whether some ancestor of the cursor is folded, so it wouldn't be shown
+    fn is_folded_away(&self, cursor: &LeafCursor) -> bool {
+        let (top_index, mut path) = cursor.to_path().strip_position();
+        while path.pop().is_some() {
+            if self.folds.contains(&(top_index, path.clone())) {
+                return true;
+            }
+        }
+        false
     }
     pub fn advance_cursor(&mut self) {
         let visible_range = self.visible_range(&self.folds);
@@ -400,15 +825,63 @@ impl JsonView {
             self.scroll.advance(&self.folds, self.rect.width);
         }
     }
-    pub fn save_to(&self, path: &str) -> std::io::Result<()> {
-        let mut file = std::fs::File::create(path)?;
-        for (i, v) in self.values.iter().enumerate() {
-            if i != 0 {
-                write!(file, "\n")?;
-            }
-            serde_json::to_writer_pretty(&mut file, v)?;
+    // Marks the differences from another view, and folds away everything that's the same.
+    pub fn set_diff(&mut self, marks: DiffMarks, folds: HashSet<(usize, Vec<usize>)>) {
+        if self.diff.is_none() {
+            self.before_diff = Some(SavedState {
+                folds: self.folds.clone(),
+                cursor: self.cursor.to_path(),
+            });
         }
-        Ok(())
+        self.diff = Some(Rc::new(marks));
+        self.folds = folds;
+        self.cursor = LeafCursor::new(self.values.clone()).expect("values should still exist");
+        self.scroll = GlobalCursor::new(self.values.clone(), self.rect.width, &self.folds)
+            .expect("values should still exist");
+    }
+    // Replaces the marks once either side has changed, leaving the cursor and folds alone
+    pub fn update_diff(&mut self, marks: DiffMarks) {
+        self.diff = Some(Rc::new(marks));
+    }
+    pub fn clear_diff(&mut self) {
+        self.diff = None;
+        if let Some(SavedState { folds, cursor }) = self.before_diff.take() {
+            self.restore_state(folds, Some(&cursor));
+            self.scroll_to_cursor();
+        }
+    }
+    // Moves the cursor to the next (or previous) difference, wrapping around at the ends.
+    pub fn jump_to_diff(&mut self, reverse: bool) -> Option<()> {
+        let marks = self.diff.clone()?;
+        let is_marked = |cursor: &LeafCursor| {
+            cursor.focus_position != FocusPosition::End
+                && marks.contains_key(&cursor.to_path().strip_position())
+        };
+        let hit = if reverse {
+            self.cursor.clone().search_back_by(is_marked)?
+        } else {
+            self.cursor.clone().search_by(is_marked)?
+        };
+        self.cursor = hit;
+        self.unfold_around_cursor();
+        if !self
+            .visible_range(&self.folds)
+            .contains_value(&self.cursor.to_path())
+        {
+            self.scroll_to_cursor();
+        }
+        Some(())
+    }
+    pub fn save_to(
+        &self,
+        path: &str,
+        format: Format,
+        layout: JsonLayout,
+        backup: bool,
+    ) -> io::Result<()> {
+        write_atomically(Path::new(path), backup, |w| {
+            format.write_with_layout(w, &self.values, layout)
+        })
     }
 }
 
@@ -448,12 +921,20 @@ impl GlobalPathRange {
 
 #[cfg(test)]
 mod tests {
-    use super::JsonView;
-    use crate::{cursor::GlobalCursor, jq::jv::JV, testing::arb_json};
+    use super::{JsonView, ViewFrame, ViewTree, ViewTreeIndex};
+    use crate::{
+        cursor::{FocusPosition, GlobalCursor},
+        jq::jv::JV,
+        layout::JexLayout,
+        testing::arb_json,
+    };
     use pretty_assertions::assert_eq;
     use proptest::proptest;
-    use serde_json::{Deserializer, Value};
-    use std::{collections::HashSet, fs, io};
+    use serde_json::{json, Deserializer, Value};
+    use std::{
+        collections::{HashMap, HashSet},
+        fs, io,
+    };
     use tui::layout::Rect;
     const DUMMY_RECT: Rect = Rect {
         x: 1,
@@ -553,4 +1034,225 @@ mod tests {
         let view = JsonView::new(jsons, TINY_RECT).unwrap();
         view.render(TINY_RECT, true);
     }
+    #[test]
+    fn unit_go_to_path() {
+        let values: Vec<JV> = vec![(&json!({"a": [1.0, {"b": null}], "c": null})).into()];
+        let mut view = JsonView::new(values, DUMMY_RECT).unwrap();
+        view.folds = vec![(0, vec![]), (0, vec![0]), (0, vec![0, 1])]
+            .into_iter()
+            .collect();
+        view.go_to_path(".a[1].b").unwrap();
+        assert_eq!(view.cursor.to_path().strip_position(), (0, vec![0, 1, 0]));
+        assert_eq!(view.cursor.focus_position, FocusPosition::Value);
+        assert!(view.folds.is_empty());
+        assert!(view.go_to_path(".c[0]").is_err());
+        assert_eq!(view.cursor.to_path().strip_position(), (0, vec![0, 1, 0]));
+    }
+    #[test]
+    fn unit_fold_commands() {
+        let values: Vec<JV> = vec![
+            (&json!({"a": [1.0, {"b": []}], "c": {"d": null}, "e": 2.0})).into(),
+            (&json!([[]])).into(),
+        ];
+        let mut view = JsonView::new(values, DUMMY_RECT).unwrap();
+        let folds = |folds: Vec<(usize, Vec<usize>)>| folds.into_iter().collect::<HashSet<_>>();
+        view.fold_to_depth(1);
+        assert_eq!(
+            view.folds,
+            folds(vec![
+                (0, vec![0]),
+                (0, vec![1]),
+                (0, vec![0, 1]),
+                (0, vec![0, 1, 0]),
+                (1, vec![0])
+            ])
+        );
+        view.fold_to_depth(0);
+        assert!(view.folds.contains(&(0, vec![])));
+        view.unfold_all();
+        assert!(view.folds.is_empty());
+        // Folding an ancestor moves the cursor out to it
+        view.go_to_path(".a[1].b").unwrap();
+        view.fold_to_depth(2);
+        assert_eq!(view.cursor.to_path().strip_position(), (0, vec![0, 1]));
+        view.unfold_all();
+        view.go_to_path(".a").unwrap();
+        view.fold_siblings();
+        assert_eq!(view.folds, folds(vec![(0, vec![1])]));
+        view.toggle_fold_recursive();
+        assert_eq!(
+            view.folds,
+            folds(vec![
+                (0, vec![0]),
+                (0, vec![1]),
+                (0, vec![0, 1]),
+                (0, vec![0, 1, 0])
+            ])
+        );
+        view.toggle_fold_recursive();
+        assert_eq!(view.folds, folds(vec![(0, vec![1])]));
+        view.go_to_path(".e").unwrap();
+        view.toggle_fold_recursive();
+        assert_eq!(view.folds, folds(vec![(0, vec![1])]));
+    }
+    #[test]
+    fn unit_fold_rules_rerun() {
+        let layout = JexLayout::new(Rect::new(0, 0, 100, 40), false);
+        let values: Vec<JV> = vec![(&json!({"items": [{"tags": [1.0]}, {"tags": []}]})).into()];
+        let mut tree = ViewTree::new(values, "root".to_string(), layout);
+        tree.push_trivial_child(layout.right);
+        tree.children[0].0 = ".items[]".to_string();
+        tree.rerun_descendants(layout.right);
+        let expected: HashSet<_> = vec![(0, vec![0]), (1, vec![0])].into_iter().collect();
+        let child = &mut tree.children[0].1.view_frame;
+        child.add_fold_rule("key tags".parse().unwrap());
+        if let super::View::Json(Some(view)) = &mut child.view {
+            assert_eq!(view.folds, expected);
+            view.folds.clear();
+        }
+        // The rules fold the new results when the query reruns
+        tree.rerun_descendants(layout.right);
+        match &tree.children[0].1.view_frame.view {
+            super::View::Json(Some(view)) => assert_eq!(view.folds, expected),
+            _ => panic!("Query should have succeeded"),
+        }
+    }
+    #[test]
+    fn unit_append_loaded() {
+        let layout = JexLayout::new(Rect::new(0, 0, 100, 40), false);
+        let mut tree = ViewTree::new(Vec::new(), "root".to_string(), layout);
+        tree.push_trivial_child(layout.right);
+        tree.children[0].0 = ".items[]".to_string();
+        tree.children[0].1.push_trivial_child(layout.right);
+        tree.children[0].1.children[0].0 = ".a".to_string();
+        let batch = |value: Value| vec![JV::from(&value)];
+        let mut programs = HashMap::new();
+        tree.append_loaded(
+            batch(json!({"items": [{"a": [1.0]}]})),
+            layout,
+            &mut programs,
+        );
+        if let super::View::Json(Some(view)) = &mut tree.children[0].1.view_frame.view {
+            view.toggle_fold();
+        }
+        tree.append_loaded(
+            batch(json!({"items": [{"a": [2.0]}, {"a": 3.0}]})),
+            layout,
+            &mut programs,
+        );
+        // Each query was compiled once, on the first batch
+        assert_eq!(programs.len(), 3);
+        let child = match &tree.children[0].1.view_frame.view {
+            super::View::Json(Some(view)) => view,
+            _ => panic!("Query should have succeeded"),
+        };
+        let values: Vec<Value> = child.values.iter().map(Value::from).collect();
+        assert_eq!(
+            values,
+            vec![json!({"a": [1.0]}), json!({"a": [2.0]}), json!({"a": 3.0})]
+        );
+        // Appending keeps the folds
+        let expected: HashSet<_> = vec![(0, vec![])].into_iter().collect();
+        assert_eq!(child.folds, expected);
+        let grandchild = match &tree.children[0].1.children[0].1.view_frame.view {
+            super::View::Json(Some(view)) => view,
+            _ => panic!("Query should have succeeded"),
+        };
+        let values: Vec<Value> = grandchild.values.iter().map(Value::from).collect();
+        assert_eq!(values, vec![json!([1.0]), json!([2.0]), json!(3.0)]);
+        // Once a query fails, it stays failed
+        tree.append_loaded(batch(json!({"items": 1.0})), layout, &mut programs);
+        assert!(matches!(
+            tree.children[0].1.view_frame.view,
+            super::View::Error(_)
+        ));
+        assert!(matches!(
+            tree.children[0].1.children[0].1.view_frame.view,
+            super::View::Json(None)
+        ));
+    }
+    #[test]
+    fn unit_diff_restores_state() {
+        let values: Vec<JV> = vec![(&json!({"a": [1.0, 2.0], "b": {"c": null}})).into()];
+        let mut view = JsonView::new(values, DUMMY_RECT).unwrap();
+        view.go_to_path(".b.c").unwrap();
+        view.folds.insert((0, vec![0]));
+        let folds = view.folds.clone();
+        let diff_folds: HashSet<_> = vec![(0, vec![1])].into_iter().collect();
+        view.set_diff(Default::default(), diff_folds.clone());
+        assert_eq!(view.folds, diff_folds);
+        // Refreshing the diff leaves the cursor and folds where they are, and doesn't lose what it
+        // started from
+        view.go_to_path(".a").unwrap();
+        view.update_diff(Default::default());
+        assert_eq!(view.folds, diff_folds);
+        assert_eq!(view.cursor.jq_path(), ".a");
+        view.clear_diff();
+        assert!(view.diff.is_none());
+        assert_eq!(view.folds, folds);
+        assert_eq!(view.cursor.jq_path(), ".b.c");
+    }
+    #[test]
+    fn unit_click() {
+        let values: Vec<JV> = vec![(&json!({"a": [1.0, 2.0], "b": null})).into()];
+        let mut view = JsonView::new(values, DUMMY_RECT).unwrap();
+        // Line 1 is `"a" : [`, with the bracket in column 8
+        view.click(1, 1);
+        assert_eq!(view.cursor.to_path().strip_position(), (0, vec![0]));
+        assert_eq!(view.cursor.focus_position, FocusPosition::Start);
+        assert!(view.folds.is_empty());
+        view.click(8, 1);
+        let expected: HashSet<_> = vec![(0, vec![0])].into_iter().collect();
+        assert_eq!(view.folds, expected);
+        // With the array folded, `"b" : null` moves up to line 2
+        view.click(10, 2);
+        assert_eq!(view.cursor.to_path().strip_position(), (0, vec![1]));
+        assert_eq!(view.folds, expected);
+        view.click(0, 10);
+        assert_eq!(view.cursor.to_path().strip_position(), (0, vec![1]));
+    }
+    #[test]
+    fn unit_index_at_row() {
+        let leaf = |name: &str| ViewTree {
+            view_frame: ViewFrame {
+                view: super::View::Json(None),
+                name: name.to_string(),
+                fold_rules: Vec::new(),
+            },
+            children: Vec::new(),
+        };
+        let mut child = leaf("child");
+        child.children.push((".".to_string(), leaf("grandchild")));
+        let mut root = leaf("root");
+        root.children.push((".".to_string(), child));
+        root.children.push((".".to_string(), leaf("second")));
+        assert_eq!(root.index_at_row(0), None);
+        let index = |parent: Vec<usize>, child| Some(ViewTreeIndex { parent, child });
+        assert_eq!(root.index_at_row(1), index(vec![], 0));
+        assert_eq!(root.index_at_row(2), index(vec![0], 0));
+        assert_eq!(root.index_at_row(3), index(vec![], 1));
+        assert_eq!(root.index_at_row(4), None);
+    }
+    #[test]
+    fn unit_queries() {
+        let leaf = || ViewTree {
+            view_frame: ViewFrame {
+                view: super::View::Json(None),
+                name: "view".to_string(),
+                fold_rules: Vec::new(),
+            },
+            children: Vec::new(),
+        };
+        let mut child = leaf();
+        child.children.push((".name".to_string(), leaf()));
+        let mut root = leaf();
+        root.children.push((".items[]".to_string(), child));
+        let index = |parent: Vec<usize>, child| ViewTreeIndex { parent, child };
+        assert_eq!(root.queries(&index(vec![], 0)), Some(vec![".items[]"]));
+        assert_eq!(
+            root.queries(&index(vec![0], 0)),
+            Some(vec![".items[]", ".name"])
+        );
+        assert_eq!(root.queries(&index(vec![0], 1)), None);
+    }
 }